@@ -29,9 +29,9 @@
 //! The `Message` union has two representations:
 //!
 //! 1. `Request`: The register context needed to perform a request or syscall. This includes an identifier
-//! and up to the 6 maximum syscall parameter registers expected by the Linux syscall ABI.
+//!    and up to the 6 maximum syscall parameter registers expected by the Linux syscall ABI.
 //! 2. `Reply`: A response from the host. This representation exists to cater to how each architecture
-//! indicates a return value.
+//!    indicates a return value.
 //!
 //! The `Message` union serves as the header for a `Block` struct, which will be examined next.
 //!
@@ -57,23 +57,22 @@
 //! 1. The shim traps all syscalls, and notices this is a `write` syscall.
 //! 1. The shim writes an empty `Block` onto the page it shares with the untrusted host.
 //! 1. The shim copies the bytes that the workload wants to write onto the data region of the `Block`. It is now
-//! accessible to the host.
+//!    accessible to the host.
 //! 1. The shim modifies the `Message` header to be a `Request` variant. In the case of the `write` syscall, the shim:
 //!     1. Sets the request `num` to the Linux integral value for `SYS_write`.
 //!     1. Furnishes the register context's syscall arguments:
 //!         1. `arg[0]` = The file descriptor to write to.
 //!         1. `arg[1]` = The address _within the `Block`_ where the bytes have been copied to.
 //!         1. `arg[2]` = The number of bytes that the `write` syscall should emit from the bytes pointed to
-//!         in the second parameter.
+//!            in the second parameter.
 //! 1. The shim yields control to the untrusted host, in which host-side Enarx code realizes it must proxy a syscall.
 //! 1. The host-side Enarx code can invoke the syscall immediately using the values in the `Block`'s `Message` header.
 //! 1. Once the syscall is complete, the host-side Enarx code can update the `Block`'s header and set it to a
-//! `Reply` variant of the `Message` union and write the syscall return code to it.
+//!    `Reply` variant of the `Message` union and write the syscall return code to it.
 //! 1. The host-side Enarx code returns control to the shim.
 //! 1. The shim examines the `Reply` in the `Message` header of the `Block` and propagates any mutated data back to
-//! the protected address space. It may then return control to its workload.
+//!    the protected address space. It may then return control to its workload.
 
-#![cfg_attr(feature = "asm", feature(asm))]
 #![deny(missing_docs)]
 #![deny(clippy::all)]
 #![cfg_attr(not(test), no_std)]
@@ -83,6 +82,8 @@ pub mod syscall;
 mod tests;
 pub mod untrusted;
 
+#[cfg(feature = "asm")]
+use core::arch::asm;
 use core::mem::size_of;
 use core::mem::MaybeUninit;
 use core::ptr::NonNull;
@@ -125,7 +126,7 @@ pub const MAX_UDP_PACKET_SIZE: usize = 65507;
 #[macro_export]
 macro_rules! request {
     ($num:expr) => {
-        $crate::Request { num: $num.into(), arg: [Register::default(); 7] }
+        $crate::Request { num: $num.into(), arg: Default::default() }
     };
 
     ($num:expr => $($arg:expr),*) => {{
@@ -167,22 +168,83 @@ pub struct Request {
 impl Request {
     /// Issues the requested syscall and returns the reply
     ///
+    /// Without the `asm` feature, the syscall is issued via `libc::syscall()`,
+    /// which only returns `rax`, so the second return register is always zero.
+    ///
     /// # Safety
     ///
     /// This function is unsafe because syscalls can't be made generically safe.
-    #[cfg(feature = "asm")]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
     pub unsafe fn syscall(&self) -> Reply {
+        // The kernel keeps the state to restart an interrupted syscall in the
+        // calling task, which does not survive the trip across the sallyport.
+        // Make the Keep re-issue the original syscall instead.
+        if i64::from(self.num) == libc::SYS_restart_syscall {
+            return Err(libc::EINTR).into();
+        }
+
+        self.execute()
+    }
+
+    /// Issues the requested syscall, if `filter` allows it, and returns the reply
+    ///
+    /// Syscalls not allowed by `filter` are never executed. They fail with `EPERM` instead.
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because syscalls can't be made generically safe.
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    pub unsafe fn syscall_filtered(&self, filter: &Filter) -> Reply {
+        if !filter.allows(usize::from(self.num) as _) {
+            return Err(libc::EPERM).into();
+        }
+
+        self.syscall()
+    }
+
+    /// Issues the requested syscall, if `policy` permits it, and returns the reply
+    ///
+    /// Syscalls `policy` refuses are never executed. They fail with the error
+    /// returned by [`Policy::check`] instead. Permitted syscalls are issued
+    /// with the arguments rewritten by [`Policy::sanitize`].
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because syscalls can't be made generically safe.
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    pub unsafe fn syscall_restricted(&self, policy: &Policy) -> Reply {
+        if let Err(e) = policy.check(self) {
+            return Err(e).into();
+        }
+
+        let mut req = *self;
+        policy.sanitize(&mut req);
+        req.syscall()
+    }
+
+    /// Translates the syscall number with `map`, then issues the syscall and returns the reply
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because syscalls can't be made generically safe.
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    pub unsafe fn syscall_mapped(&self, map: &NumberMap) -> Reply {
+        let mut req = *self;
+        req.num = (map.translate(usize::from(self.num) as _) as usize).into();
+        req.syscall()
+    }
+
+    #[cfg(feature = "asm")]
+    unsafe fn execute(&self) -> Reply {
         let rax: usize;
         let rdx: usize;
 
         if i64::from(self.num) == libc::SYS_clock_gettime {
-            let mut ret = libc::clock_gettime(usize::from(self.arg[0]) as _, self.arg[1].into());
-            if ret != 0 {
-                ret = *libc::__errno_location();
-            }
-            Reply {
-                ret: [ret.into(), 0.into()],
-                err: Default::default(),
+            // Encode like the `libc::syscall()` fallback does
+            if libc::clock_gettime(usize::from(self.arg[0]) as _, self.arg[1].into()) != 0 {
+                Err(*libc::__errno_location()).into()
+            } else {
+                Ok([0.into(), 0.into()]).into()
             }
         } else {
             asm!(
@@ -198,14 +260,302 @@ impl Request {
             lateout("r11") _, // clobbered
             );
 
-            Reply {
+            let reply = Reply {
                 ret: [rax.into(), rdx.into()],
                 err: Default::default(),
+            };
+
+            // Re-encode errors, so no kernel-internal restart errno reaches the Keep
+            Result::from(reply).into()
+        }
+    }
+
+    #[cfg(not(feature = "asm"))]
+    unsafe fn execute(&self) -> Reply {
+        let ret = libc::syscall(
+            usize::from(self.num) as _,
+            usize::from(self.arg[0]),
+            usize::from(self.arg[1]),
+            usize::from(self.arg[2]),
+            usize::from(self.arg[3]),
+            usize::from(self.arg[4]),
+            usize::from(self.arg[5]),
+        );
+
+        // libc only reports the errno range as an error, and passes other
+        // negative values (e.g. large unsigned offsets) back unchanged.
+        if ret == -1 {
+            Err(*libc::__errno_location()).into()
+        } else {
+            Ok([(ret as usize).into(), 0.into()]).into()
+        }
+    }
+}
+
+/// A set of syscall numbers the host is willing to execute on behalf of the shim
+///
+/// The default filter allows exactly the syscalls the handlers in [`syscall`]
+/// proxy to the host.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Filter([u64; 8]);
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+impl Filter {
+    /// A filter, which allows no syscalls at all
+    pub const fn empty() -> Self {
+        Self([0; 8])
+    }
+
+    /// Allows the syscall `num`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num` is negative or not below 512.
+    pub fn allow(&mut self, num: libc::c_long) -> &mut Self {
+        assert!((0..512).contains(&num), "syscall number out of range");
+        self.0[num as usize / 64] |= 1 << (num as usize % 64);
+        self
+    }
+
+    /// Denies the syscall `num`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num` is negative or not below 512.
+    pub fn deny(&mut self, num: libc::c_long) -> &mut Self {
+        assert!((0..512).contains(&num), "syscall number out of range");
+        self.0[num as usize / 64] &= !(1 << (num as usize % 64));
+        self
+    }
+
+    /// Returns whether the syscall `num` is allowed
+    pub fn allows(&self, num: libc::c_long) -> bool {
+        (0..512).contains(&num) && self.0[num as usize / 64] & 1 << (num as usize % 64) != 0
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+impl Default for Filter {
+    fn default() -> Self {
+        let mut filter = Self::empty();
+        for &num in &[
+            libc::SYS_accept4,
+            libc::SYS_bind,
+            libc::SYS_clock_gettime,
+            libc::SYS_clock_nanosleep,
+            libc::SYS_close,
+            libc::SYS_close_range,
+            libc::SYS_connect,
+            libc::SYS_dup,
+            libc::SYS_dup2,
+            libc::SYS_dup3,
+            libc::SYS_epoll_create1,
+            libc::SYS_epoll_ctl,
+            libc::SYS_epoll_wait,
+            libc::SYS_eventfd2,
+            libc::SYS_exit,
+            libc::SYS_exit_group,
+            libc::SYS_fcntl,
+            libc::SYS_fstat,
+            libc::SYS_getsockname,
+            libc::SYS_getsockopt,
+            libc::SYS_ioctl,
+            libc::SYS_listen,
+            libc::SYS_lseek,
+            libc::SYS_memfd_create,
+            libc::SYS_openat,
+            libc::SYS_pipe,
+            libc::SYS_poll,
+            libc::SYS_prlimit64,
+            libc::SYS_read,
+            libc::SYS_readahead,
+            libc::SYS_recvfrom,
+            libc::SYS_sched_yield,
+            libc::SYS_sendto,
+            libc::SYS_setsockopt,
+            libc::SYS_socket,
+            libc::SYS_sync,
+            libc::SYS_syncfs,
+            libc::SYS_write,
+        ] {
+            filter.allow(num);
+        }
+        filter
+    }
+}
+
+/// Restrictions on what the host does on behalf of the shim, beyond syscall numbers
+///
+/// Unlike [`Filter`], a policy may look at and rewrite the syscall arguments.
+/// The default policy permits everything.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[derive(Copy, Clone, Default, PartialEq, Debug)]
+pub struct Policy {
+    /// The syscalls a read-only keep may issue, if the keep is read-only
+    read_only: Option<Filter>,
+    reject_creat_without_mode: bool,
+    reject_tmpfile: bool,
+    strip_direct: bool,
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+impl Policy {
+    /// A policy for read-only keeps
+    ///
+    /// Only the syscalls in [`Filter::default()`] which can't write to or
+    /// modify files are permitted. That excludes `write()` and `ioctl()`.
+    /// `open()` and `openat()` are permitted without write access, `O_CREAT`
+    /// or `O_TRUNC`. Everything else fails with `EROFS`.
+    pub fn read_only() -> Self {
+        let mut filter = Filter::default();
+        filter.deny(libc::SYS_write).deny(libc::SYS_ioctl);
+        Self {
+            read_only: Some(filter),
+            ..Default::default()
+        }
+    }
+
+    /// Makes `open()` and `openat()` with `O_CREAT`, but without any
+    /// permission bits in `mode`, fail with `EINVAL`
+    pub fn reject_creat_without_mode(&mut self) -> &mut Self {
+        self.reject_creat_without_mode = true;
+        self
+    }
+
+    /// Makes `open()` and `openat()` with `O_TMPFILE` fail with `EINVAL`
+    pub fn reject_tmpfile(&mut self) -> &mut Self {
+        self.reject_tmpfile = true;
+        self
+    }
+
+    /// Removes `O_DIRECT` from the flags of `open()` and `openat()`
+    pub fn strip_direct(&mut self) -> &mut Self {
+        self.strip_direct = true;
+        self
+    }
+
+    /// Returns the indices of the flags and mode arguments of `open()` and `openat()`
+    fn open_args(req: &Request) -> Option<(usize, usize)> {
+        match i64::from(req.num) {
+            libc::SYS_open => Some((1, 2)),
+            libc::SYS_openat => Some((2, 3)),
+            _ => None,
+        }
+    }
+
+    /// Checks whether `req` may be executed
+    ///
+    /// Returns the error the syscall should fail with, if it may not.
+    pub fn check(&self, req: &Request) -> core::result::Result<(), libc::c_int> {
+        let num = usize::from(req.num) as libc::c_long;
+        let (flags, mode) = match Self::open_args(req) {
+            Some((flags, mode)) => (
+                usize::from(req.arg[flags]) as libc::c_int,
+                usize::from(req.arg[mode]) as libc::mode_t,
+            ),
+            None => {
+                return match self.read_only {
+                    Some(ref filter) if !filter.allows(num) => Err(libc::EROFS),
+                    _ => Ok(()),
+                }
+            }
+        };
+
+        if self.read_only.is_some()
+            && (flags & libc::O_ACCMODE != libc::O_RDONLY
+                || flags & (libc::O_CREAT | libc::O_TRUNC) != 0)
+        {
+            return Err(libc::EROFS);
+        }
+
+        if self.reject_creat_without_mode && flags & libc::O_CREAT != 0 && mode & 0o7777 == 0 {
+            return Err(libc::EINVAL);
+        }
+
+        if self.reject_tmpfile && flags & libc::O_TMPFILE == libc::O_TMPFILE {
+            return Err(libc::EINVAL);
+        }
+
+        Ok(())
+    }
+
+    /// Rewrites the arguments of `req`, which [`Policy::check`] permitted
+    pub fn sanitize(&self, req: &mut Request) {
+        if let Some((flags, _)) = Self::open_args(req) {
+            if self.strip_direct {
+                let stripped = usize::from(req.arg[flags]) & !(libc::O_DIRECT as usize);
+                req.arg[flags] = stripped.into();
             }
         }
     }
 }
 
+/// A table translating the shim's syscall numbers to the host's
+///
+/// Guests built against a different kernel ABI may use different syscall
+/// numbers than the host. Numbers without an entry pass through unchanged.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[derive(Clone, PartialEq, Debug)]
+pub struct NumberMap([libc::c_long; 512]);
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+impl NumberMap {
+    /// A map, which passes all syscall numbers through unchanged
+    pub const fn identity() -> Self {
+        let mut map = [0; 512];
+        let mut num = 0;
+        while num < map.len() {
+            map[num] = num as _;
+            num += 1;
+        }
+        Self(map)
+    }
+
+    /// Translates the shim's syscall `from` to the host's syscall `to`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `from` is negative or not below 512.
+    pub fn map(&mut self, from: libc::c_long, to: libc::c_long) -> &mut Self {
+        assert!((0..512).contains(&from), "syscall number out of range");
+        self.0[from as usize] = to;
+        self
+    }
+
+    /// Returns the host's syscall number for the shim's syscall `num`
+    pub fn translate(&self, num: libc::c_long) -> libc::c_long {
+        if (0..512).contains(&num) {
+            self.0[num as usize]
+        } else {
+            num
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+impl Default for NumberMap {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// Rewrites or refuses the paths of syscalls the host executes on behalf of the shim
+///
+/// See [`Block::syscall_path_filtered`]. Only the paths of `open()` and
+/// `openat()` are passed to the filter, so combine it with a [`Filter`], which
+/// allows no other syscalls taking paths, such as [`Filter::default()`].
+pub trait PathFilter {
+    /// Inspects and optionally rewrites the path staged for a syscall
+    ///
+    /// `path[..len]` is the path and `path[len]` its terminating NUL. The rest
+    /// of `path` is the free space after it in the block, so the whole of
+    /// `path` may be overwritten, as long as the result stays NUL-terminated.
+    ///
+    /// Returns the error the syscall should fail with, if the path is refused.
+    fn filter(&self, path: &mut [u8], len: usize) -> core::result::Result<(), libc::c_int>;
+}
+
 /// A reply
 ///
 /// The `Reply` struct is the most minimal representation of the register context
@@ -224,6 +574,29 @@ pub struct Reply {
     err: Register<usize>,
 }
 
+// Kernel-internal restart errnos, which are not defined in libc
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const ERESTARTSYS: libc::c_int = 512;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const ERESTARTNOINTR: libc::c_int = 513;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const ERESTARTNOHAND: libc::c_int = 514;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const ERESTART_RESTARTBLOCK: libc::c_int = 516;
+
+/// Maps the kernel-internal restart errnos to `EINTR`
+///
+/// These must never be seen by userspace. The Keep can't make use of the
+/// kernel's restart machinery, so it is told the syscall was interrupted.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[inline]
+fn restart_to_eintr(err: libc::c_int) -> libc::c_int {
+    match err {
+        ERESTARTSYS | ERESTARTNOINTR | ERESTARTNOHAND | ERESTART_RESTARTBLOCK => libc::EINTR,
+        err => err,
+    }
+}
+
 /// The result of a syscall
 ///
 /// This is isomorphic with `Reply`, which is like `Result`, but has a stable
@@ -240,7 +613,7 @@ impl From<Result> for Reply {
                 err: Default::default(),
             },
             Err(val) => Self {
-                ret: [(-val as usize).into(), Default::default()],
+                ret: [(-restart_to_eintr(val) as usize).into(), Default::default()],
                 err: Default::default(),
             },
         }
@@ -307,13 +680,53 @@ impl Block {
     /// Returns the capacity of `Block.buf`
     pub const fn buf_capacity() -> usize {
         // At least MAX_UDP_PACKET_SIZE rounded up Page::size() alignment
-        (MAX_UDP_PACKET_SIZE + size_of::<Message>() + Page::SIZE - 1) / Page::SIZE * Page::SIZE
+        ((MAX_UDP_PACKET_SIZE + size_of::<Message>() + Page::SIZE - 1) & !(Page::SIZE - 1))
             - size_of::<Message>()
     }
 
+    /// Issues the requested syscall, after passing its path through `filter`, and returns the reply
+    ///
+    /// The path must be staged in `buf`, or the syscall fails with `EFAULT`.
+    /// If it is not NUL-terminated before and after filtering, the syscall
+    /// fails with `ENAMETOOLONG`. Syscalls without a path are issued unchanged.
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because syscalls can't be made generically safe,
+    /// and because `msg` must hold a request.
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    pub unsafe fn syscall_path_filtered(&mut self, filter: &impl PathFilter) -> Reply {
+        let req = self.msg.req;
+        let arg = match i64::from(req.num) {
+            libc::SYS_open => 0,
+            libc::SYS_openat => 1,
+            _ => return req.syscall(),
+        };
+
+        let base = self.buf.as_ptr() as usize;
+        let offset = match usize::from(req.arg[arg]).checked_sub(base) {
+            Some(offset) if offset < self.buf.len() => offset,
+            _ => return Err(libc::EFAULT).into(),
+        };
+        let path = &mut self.buf[offset..];
+
+        let len = match path.iter().position(|&b| b == 0) {
+            Some(len) => len,
+            None => return Err(libc::ENAMETOOLONG).into(),
+        };
+        if let Err(e) = filter.filter(path, len) {
+            return Err(e).into();
+        }
+        if !path.contains(&0) {
+            return Err(libc::ENAMETOOLONG).into();
+        }
+
+        req.syscall()
+    }
+
     /// Returns a Cursor for the Block
     #[allow(dead_code)]
-    pub fn cursor(&mut self) -> Cursor {
+    pub fn cursor(&mut self) -> Cursor<'_> {
         Cursor(&mut self.buf)
     }
 }
@@ -21,7 +21,7 @@ pub trait BaseSyscallHandler {
     fn translate_shim_to_host_addr<T>(buf: *const T) -> usize;
 
     /// Returns a new `Cursor` for the sallyport `Block`
-    fn new_cursor(&mut self) -> Cursor;
+    fn new_cursor(&mut self) -> Cursor<'_>;
 
     /// Report an unknown syscall
     #[allow(clippy::too_many_arguments)]
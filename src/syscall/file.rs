@@ -9,6 +9,50 @@ use core::mem::MaybeUninit;
 
 /// file syscalls
 pub trait FileSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
+    /// The maximum number of `iovec`s accepted by `readv()` and `writev()`
+    ///
+    /// Defaults to the kernel's `UIO_MAXIOV`.
+    const MAX_IOVCNT: libc::c_int = libc::UIO_MAXIOV;
+
+    /// syscall
+    fn open(
+        &mut self,
+        pathname: UntrustedRef<u8>,
+        flags: libc::c_int,
+        mode: libc::mode_t,
+    ) -> Result {
+        self.openat(libc::AT_FDCWD, pathname, flags, mode)
+    }
+
+    /// syscall
+    fn openat(
+        &mut self,
+        dirfd: libc::c_int,
+        pathname: UntrustedRef<u8>,
+        flags: libc::c_int,
+        mode: libc::mode_t,
+    ) -> Result {
+        self.trace("openat", 4);
+
+        // PATH_MAX includes the terminating NUL byte
+        let pathname = validate_cstr(pathname, libc::PATH_MAX as usize - 1, self)?;
+
+        let c = self.new_cursor();
+        let (_, buf) = c.copy_from_slice(pathname).or(Err(libc::EMSGSIZE))?;
+        let host_virt = Self::translate_shim_to_host_addr(buf.as_ptr());
+
+        let ret =
+            unsafe { self.proxy(request!(libc::SYS_openat => dirfd, host_virt, flags, mode))? };
+
+        let fd: usize = ret[0].into();
+
+        if fd > libc::c_int::MAX as usize {
+            self.attacked();
+        }
+
+        Ok(ret)
+    }
+
     /// syscall
     fn close(&mut self, fd: libc::c_int) -> Result {
         self.trace("close", 1);
@@ -16,6 +60,17 @@ pub trait FileSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         Ok(ret)
     }
 
+    /// syscall
+    fn close_range(
+        &mut self,
+        first: libc::c_uint,
+        last: libc::c_uint,
+        flags: libc::c_uint,
+    ) -> Result {
+        self.trace("close_range", 3);
+        unsafe { self.proxy(request!(libc::SYS_close_range => first, last, flags)) }
+    }
+
     /// syscall
     fn read(&mut self, fd: libc::c_int, buf: UntrustedRefMut<u8>, count: libc::size_t) -> Result {
         self.trace("read", 4);
@@ -56,14 +111,30 @@ pub trait FileSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         iovcnt: libc::c_int,
     ) -> Result {
         self.trace("readv", 3);
+
+        if !(0..=Self::MAX_IOVCNT).contains(&iovcnt) {
+            return Err(libc::EINVAL);
+        }
+
         // FIXME: this is not an ideal implementation of readv, but for the sake
         // of simplicity this readv implementation behaves very much like how the
         // Linux kernel would for a module that does not support readv, but does
         // support read.
         let mut bytes_read = 0usize;
         for vec in iovec.validate_slice(iovcnt, self).ok_or(libc::EFAULT)? {
-            let r = self.read(fd, (vec.iov_base as *mut u8).into(), vec.iov_len as _)?;
-            bytes_read = bytes_read.checked_add(r[0].into()).unwrap();
+            let r = match self.read(fd, (vec.iov_base as *mut u8).into(), vec.iov_len as _) {
+                Ok(r) => usize::from(r[0]),
+                // Report the bytes read so far, like the kernel does.
+                Err(_) if bytes_read > 0 => break,
+                Err(e) => return Err(e),
+            };
+
+            bytes_read = bytes_read.checked_add(r).unwrap();
+
+            if r != vec.iov_len {
+                // There was a short read, the following buffers stay untouched.
+                break;
+            }
         }
 
         Ok([bytes_read.into(), 0.into()])
@@ -105,13 +176,22 @@ pub trait FileSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         iovcnt: libc::c_int,
     ) -> Result {
         self.trace("writev", 3);
+
+        if !(0..=Self::MAX_IOVCNT).contains(&iovcnt) {
+            return Err(libc::EINVAL);
+        }
+
         let iovec = iovec.validate_slice(iovcnt, self).ok_or(libc::EFAULT)?;
 
         let mut size = 0usize;
 
         for vec in iovec {
-            let written =
-                usize::from(self.write(fd, (vec.iov_base as *const u8).into(), vec.iov_len)?[0]);
+            let written = match self.write(fd, (vec.iov_base as *const u8).into(), vec.iov_len) {
+                Ok(r) => usize::from(r[0]),
+                // Report the bytes written so far, like the kernel does.
+                Err(_) if size > 0 => break,
+                Err(e) => return Err(e),
+            };
 
             if written > vec.iov_len {
                 self.attacked();
@@ -128,6 +208,33 @@ pub trait FileSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         Ok([size.into(), 0.into()])
     }
 
+    /// syscall
+    ///
+    /// Files with unsigned offsets (e.g. `/dev/mem`) can legitimately seek past
+    /// `off_t::MAX`, so only the errno range of the returned offset is an error.
+    fn lseek(&mut self, fd: libc::c_int, offset: libc::off_t, whence: libc::c_int) -> Result {
+        self.trace("lseek", 3);
+        unsafe { self.proxy(request!(libc::SYS_lseek => fd, offset, whence)) }
+    }
+
+    /// syscall
+    fn readahead(&mut self, fd: libc::c_int, offset: libc::off64_t, count: libc::size_t) -> Result {
+        self.trace("readahead", 3);
+        unsafe { self.proxy(request!(libc::SYS_readahead => fd, offset, count)) }
+    }
+
+    /// syscall
+    fn sync(&mut self) -> Result {
+        self.trace("sync", 0);
+        unsafe { self.proxy(request!(libc::SYS_sync)) }
+    }
+
+    /// syscall
+    fn syncfs(&mut self, fd: libc::c_int) -> Result {
+        self.trace("syncfs", 1);
+        unsafe { self.proxy(request!(libc::SYS_syncfs => fd)) }
+    }
+
     /// syscall
     fn ioctl(&mut self, fd: libc::c_int, request: libc::c_ulong, arg: usize) -> Result {
         self.trace("ioctl", 3);
@@ -206,7 +313,6 @@ pub trait FileSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         // Fake fstat(0|1|2, ...) done by glibc or rust
         match fd {
             libc::STDIN_FILENO | libc::STDOUT_FILENO | libc::STDERR_FILENO => {
-                #[allow(clippy::integer_arithmetic)]
                 const fn makedev(x: u64, y: u64) -> u64 {
                     (((x) & 0xffff_f000u64) << 32)
                         | (((x) & 0x0000_0fffu64) << 8)
@@ -252,7 +358,20 @@ pub trait FileSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
 
                 Ok(Default::default())
             }
-            _ => Err(libc::EBADF),
+            _ => {
+                let statbuf = statbuf.validate(self).ok_or(libc::EFAULT)?;
+                let c = self.new_cursor();
+
+                let (_, buf) = c.alloc::<libc::stat>(1).or(Err(libc::EMSGSIZE))?;
+                let host_virt = Self::translate_shim_to_host_addr(buf[0].as_ptr());
+
+                let result = unsafe { self.proxy(request!(libc::SYS_fstat => fd, host_virt))? };
+
+                let c = self.new_cursor();
+                *statbuf = unsafe { c.read().or(Err(libc::EMSGSIZE))?.1 };
+
+                Ok(result)
+            }
         }
     }
 
@@ -288,6 +407,10 @@ pub trait FileSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
                 //self.trace("fcntl", 3);
                 unsafe { self.proxy(request!(libc::SYS_fcntl => fd, cmd, arg)) }
             }
+            (_, libc::F_GETPIPE_SZ) => unsafe { self.proxy(request!(libc::SYS_fcntl => fd, cmd)) },
+            (_, libc::F_SETPIPE_SZ) => unsafe {
+                self.proxy(request!(libc::SYS_fcntl => fd, cmd, arg))
+            },
             (_, _) => {
                 //eprintln!("SC> fcntl({}, {}) = -EBADFD", fd, cmd);
                 Err(libc::EBADFD)
@@ -304,6 +427,11 @@ pub trait FileSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
     ) -> Result {
         self.trace("poll", 3);
 
+        if nfds == 0 {
+            // A pure timeout, which might not even pass a valid `fds` pointer
+            return unsafe { self.proxy(request!(libc::SYS_poll => 0, nfds, timeout)) };
+        }
+
         let fds = fds.validate_slice(nfds, self).ok_or(libc::EFAULT)?;
 
         let c = self.new_cursor();
@@ -345,6 +473,33 @@ pub trait FileSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         Ok(ret)
     }
 
+    /// syscall
+    fn memfd_create(&mut self, name: UntrustedRef<u8>, flags: libc::c_uint) -> Result {
+        self.trace("memfd_create", 2);
+
+        // NAME_MAX minus the "memfd:" prefix
+        const MFD_NAME_MAX_LEN: usize = 249;
+
+        let name = validate_cstr(name, MFD_NAME_MAX_LEN, self).map_err(|e| match e {
+            libc::ENAMETOOLONG => libc::EINVAL,
+            e => e,
+        })?;
+
+        let c = self.new_cursor();
+        let (_, buf) = c.copy_from_slice(name).or(Err(libc::EMSGSIZE))?;
+        let host_virt = Self::translate_shim_to_host_addr(buf.as_ptr());
+
+        let ret = unsafe { self.proxy(request!(libc::SYS_memfd_create => host_virt, flags))? };
+
+        let fd: usize = ret[0].into();
+
+        if fd > libc::c_int::MAX as usize {
+            self.attacked();
+        }
+
+        Ok(ret)
+    }
+
     /// syscall
     fn epoll_create1(&mut self, flags: libc::c_int) -> Result {
         self.trace("epoll_create1", 1);
@@ -453,3 +608,29 @@ pub trait FileSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         unsafe { self.proxy(request!(libc::SYS_dup3 => oldfd, newfd, flags)) }
     }
 }
+
+/// Validates a NUL-terminated string of at most `max_len` bytes
+///
+/// Returns the string including its terminating NUL byte, or `ENAMETOOLONG`,
+/// if it is longer than `max_len` bytes.
+fn validate_cstr<'a, V: AddressValidator>(
+    ptr: UntrustedRef<'a, u8>,
+    max_len: usize,
+    validator: &V,
+) -> core::result::Result<&'a [u8], libc::c_int> {
+    let ptr = ptr.as_ptr();
+
+    for len in 0..=max_len {
+        let byte = UntrustedRef::from(ptr.wrapping_add(len))
+            .validate(validator)
+            .ok_or(libc::EFAULT)?;
+
+        if *byte == 0 {
+            return UntrustedRef::from(ptr)
+                .validate_slice(len + 1, validator)
+                .ok_or(libc::EFAULT);
+        }
+    }
+
+    Err(libc::ENAMETOOLONG)
+}
@@ -104,6 +104,18 @@ pub struct KernelSigSet;
 /// FIXME
 pub type KernelSigAction = [u64; 4];
 
+/// `struct robust_list_head`, not defined in libc
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct RobustListHead {
+    /// The head of the list of held robust futexes
+    pub list: *const libc::c_void,
+    /// Offset of the futex word relative to a list entry
+    pub futex_offset: libc::c_long,
+    /// The entry of a futex being acquired or released
+    pub list_op_pending: *const libc::c_void,
+}
+
 /// A trait defining a shim syscall handler
 ///
 /// Implemented for each shim. Some common methods are already implemented,
@@ -182,8 +194,20 @@ pub trait SyscallHandler:
             libc::SYS_rt_sigprocmask => {
                 self.rt_sigprocmask(usize::from(a) as _, b.into(), c.into(), d.into())
             }
+            libc::SYS_set_robust_list => self.set_robust_list(a.into(), b.into()),
+            libc::SYS_get_robust_list => {
+                self.get_robust_list(usize::from(a) as _, b.into(), c.into())
+            }
             libc::SYS_sigaltstack => self.sigaltstack(a.into(), b.into()),
             libc::SYS_getpid => self.getpid(),
+            libc::SYS_getppid => self.getppid(),
+            libc::SYS_sched_yield => self.sched_yield(),
+            libc::SYS_gettid => self.gettid(),
+            libc::SYS_getsid => self.getsid(usize::from(a) as _),
+            libc::SYS_prlimit64 => {
+                self.prlimit64(usize::from(a) as _, usize::from(b) as _, c.into(), d.into())
+            }
+            libc::SYS_getrlimit => self.getrlimit(usize::from(a) as _, b.into()),
             libc::SYS_getuid => self.getuid(),
             libc::SYS_getgid => self.getgid(),
             libc::SYS_geteuid => self.geteuid(),
@@ -192,14 +216,40 @@ pub trait SyscallHandler:
             // SystemSyscallHandler
             libc::SYS_getrandom => self.getrandom(a.into(), b.into(), usize::from(c) as _),
             libc::SYS_clock_gettime => self.clock_gettime(usize::from(a) as _, b.into()),
+            libc::SYS_nanosleep => self.nanosleep(a.into(), b.into()),
+            libc::SYS_clock_nanosleep => {
+                self.clock_nanosleep(usize::from(a) as _, usize::from(b) as _, c.into(), d.into())
+            }
             libc::SYS_uname => self.uname(a.into()),
 
             // FileSyscallHandler
+            libc::SYS_open => self.open(a.into(), usize::from(b) as _, usize::from(c) as _),
+            libc::SYS_openat => self.openat(
+                usize::from(a) as _,
+                b.into(),
+                usize::from(c) as _,
+                usize::from(d) as _,
+            ),
             libc::SYS_close => self.close(a.try_into().map_err(|_| libc::EINVAL)?),
+            libc::SYS_close_range => self.close_range(
+                usize::from(a) as _,
+                usize::from(b) as _,
+                usize::from(c) as _,
+            ),
             libc::SYS_read => self.read(usize::from(a) as _, b.into(), c.into()),
             libc::SYS_readv => self.readv(usize::from(a) as _, b.into(), usize::from(c) as _),
             libc::SYS_write => self.write(usize::from(a) as _, b.into(), c.into()),
             libc::SYS_writev => self.writev(usize::from(a) as _, b.into(), usize::from(c) as _),
+            libc::SYS_lseek => self.lseek(
+                usize::from(a) as _,
+                usize::from(b) as _,
+                usize::from(c) as _,
+            ),
+            libc::SYS_readahead => {
+                self.readahead(usize::from(a) as _, usize::from(b) as _, c.into())
+            }
+            libc::SYS_sync => self.sync(),
+            libc::SYS_syncfs => self.syncfs(usize::from(a) as _),
             libc::SYS_ioctl => self.ioctl(usize::from(a) as _, b.into(), c.into()),
             libc::SYS_readlink => self.readlink(a.into(), b.into(), c.into()),
             libc::SYS_fstat => self.fstat(usize::from(a) as _, b.into()),
@@ -210,6 +260,7 @@ pub trait SyscallHandler:
             ),
             libc::SYS_poll => self.poll(a.into(), b.into(), usize::from(c) as _),
             libc::SYS_pipe => self.pipe(a.into()),
+            libc::SYS_memfd_create => self.memfd_create(a.into(), usize::from(b) as _),
             libc::SYS_epoll_create1 => self.epoll_create1(a.try_into().map_err(|_| libc::EINVAL)?),
             libc::SYS_epoll_ctl => self.epoll_ctl(
                 usize::from(a) as _,
@@ -276,6 +327,13 @@ pub trait SyscallHandler:
                 d.into(),
                 usize::from(e) as _,
             ),
+            libc::SYS_getsockopt => self.getsockopt(
+                usize::from(a) as _,
+                usize::from(b) as _,
+                usize::from(c) as _,
+                d.into(),
+                e.into(),
+            ),
 
             SYS_ENARX_GETATT => self.get_attestation(a.into(), b.into(), c.into(), d.into()),
 
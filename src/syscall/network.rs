@@ -11,7 +11,15 @@ pub trait NetworkSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
     /// syscall
     fn socket(&mut self, domain: libc::c_int, type_: libc::c_int, protocol: libc::c_int) -> Result {
         self.trace("socket", 3);
-        unsafe { self.proxy(request!(libc::SYS_socket => domain, type_, protocol)) }
+        let ret = unsafe { self.proxy(request!(libc::SYS_socket => domain, type_, protocol))? };
+
+        let fd: usize = ret[0].into();
+
+        if fd > libc::c_int::MAX as usize {
+            self.attacked();
+        }
+
+        Ok(ret)
     }
 
     /// syscall
@@ -53,7 +61,7 @@ pub trait NetworkSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         let (c, block_addr) = c.alloc::<u8>(*addrlen as _).or(Err(libc::EMSGSIZE))?;
         let (_, block_addrlen) = c.write(addrlen).or(Err(libc::EINVAL))?;
 
-        let block_addr_ptr = block_addr[0].as_ptr();
+        let block_addr_ptr = block_addr.as_ptr();
         let block_addr = Self::translate_shim_to_host_addr(block_addr_ptr);
         let block_addrlen = Self::translate_shim_to_host_addr(block_addrlen as _);
 
@@ -101,11 +109,8 @@ pub trait NetworkSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         self.trace("accept4", 4);
 
         if addr.as_ptr().is_null() {
-            return unsafe {
-                self.proxy(
-                    request!(libc::SYS_accept4 => fd, addr.as_ptr(), addrlen.as_ptr(), flags),
-                )
-            };
+            // The kernel ignores `addrlen` without `addr`, so never hand it the guest pointer
+            return unsafe { self.proxy(request!(libc::SYS_accept4 => fd, 0, 0, flags)) };
         }
 
         let addrlen = addrlen.validate(self).ok_or(libc::EFAULT)?;
@@ -115,7 +120,7 @@ pub trait NetworkSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         let (c, block_addr) = c.alloc::<u8>(*addrlen as _).or(Err(libc::EMSGSIZE))?;
         let (_, block_addrlen) = c.write(addrlen).or(Err(libc::EINVAL))?;
 
-        let block_addr_ptr = block_addr[0].as_ptr();
+        let block_addr_ptr = block_addr.as_ptr();
         let block_addr = Self::translate_shim_to_host_addr(block_addr_ptr);
         let block_addrlen = Self::translate_shim_to_host_addr(block_addrlen as _);
 
@@ -179,7 +184,11 @@ pub trait NetworkSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         // Limit the read to `Block::buf_capacity()`
         let count = usize::min(count, Block::buf_capacity());
 
-        let addrlen = addrlen.validate(self);
+        let addrlen = if src_addr.as_ptr().is_null() {
+            None
+        } else {
+            Some(addrlen.validate(self).ok_or(libc::EFAULT)?)
+        };
 
         let len = match addrlen {
             None => 0,
@@ -202,8 +211,7 @@ pub trait NetworkSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
             (src_addr.as_ptr() as usize, 0)
         } else {
             let (c, block_addr) = c.alloc::<u8>(len as _).or(Err(libc::EMSGSIZE))?;
-            let block_addr_ptr = block_addr[0].as_ptr();
-            let block_addr = Self::translate_shim_to_host_addr(block_addr_ptr);
+            let block_addr = Self::translate_shim_to_host_addr(block_addr.as_ptr());
             let (_c, block_addrlen) = c.write(addrlen.as_deref().unwrap()).or(Err(libc::EINVAL))?;
             let block_addrlen = Self::translate_shim_to_host_addr(block_addrlen as _);
             (block_addr, block_addrlen)
@@ -329,4 +337,53 @@ pub trait NetworkSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
             self.proxy(request!(libc::SYS_setsockopt => sockfd, level,optname, host_virt, optlen))
         }
     }
+
+    /// syscall
+    fn getsockopt(
+        &mut self,
+        sockfd: libc::c_int,
+        level: libc::c_int,
+        optname: libc::c_int,
+        optval: UntrustedRefMut<u8>,
+        optlen: UntrustedRefMut<libc::socklen_t>,
+    ) -> Result {
+        self.trace("getsockopt", 5);
+
+        let optlen = optlen.validate(self).ok_or(libc::EFAULT)?;
+
+        let c = self.new_cursor();
+
+        let (c, block_optval) = c.alloc::<u8>(*optlen as _).or(Err(libc::EMSGSIZE))?;
+        let (_, block_optlen) = c.write(optlen).or(Err(libc::EINVAL))?;
+
+        let block_optval = Self::translate_shim_to_host_addr(block_optval.as_ptr());
+        let block_optlen = Self::translate_shim_to_host_addr(block_optlen as _);
+
+        let ret = unsafe {
+            self.proxy(
+                request!(libc::SYS_getsockopt => sockfd, level, optname, block_optval, block_optlen),
+            )
+        }?;
+
+        unsafe {
+            let c = self.new_cursor();
+            let (c, _) = c.alloc::<u8>(*optlen as _).or(Err(libc::EMSGSIZE))?;
+            let (_, block_optlen) = c.read::<libc::socklen_t>().or(Err(libc::EMSGSIZE))?;
+
+            // Unlike the address calls, the kernel never reports more than it was given
+            if block_optlen > *optlen {
+                self.attacked();
+            }
+
+            let optval = optval.validate_slice(*optlen, self).ok_or(libc::EFAULT)?;
+
+            let c = self.new_cursor();
+            c.copy_into_slice(*optlen as _, &mut optval[..block_optlen as usize])
+                .or(Err(libc::EMSGSIZE))?;
+
+            *optlen = block_optlen;
+        }
+
+        Ok(ret)
+    }
 }
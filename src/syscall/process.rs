@@ -3,24 +3,28 @@
 //! process syscalls
 
 use super::BaseSyscallHandler;
-use crate::syscall::{KernelSigAction, KernelSigSet, FAKE_GID, FAKE_PID, FAKE_UID};
+use crate::syscall::{KernelSigAction, KernelSigSet, RobustListHead, FAKE_GID, FAKE_PID, FAKE_UID};
 use crate::untrusted::{AddressValidator, UntrustedRef, UntrustedRefMut, Validate};
 use crate::{request, Result};
+use core::mem::size_of;
 
 /// process syscalls
 pub trait ProcessSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
     /// syscall
     fn arch_prctl(&mut self, code: libc::c_int, addr: libc::c_ulong) -> Result;
 
+    /// The robust list head recorded by `set_robust_list()`
+    ///
+    /// We are only using one thread, so the handler keeps a single head,
+    /// which starts out as `0`.
+    fn robust_list_head(&mut self) -> &mut usize;
+
     /// Proxy an exit() syscall
     fn exit(&mut self, status: libc::c_int) -> ! {
         self.trace("exit", 1);
 
-        #[allow(unused_must_use)]
-        loop {
-            unsafe { self.proxy(request!(libc::SYS_exit => status)) };
-            self.attacked();
-        }
+        let _ = unsafe { self.proxy(request!(libc::SYS_exit => status)) };
+        self.attacked()
     }
 
     /// Proxy an exitgroup() syscall
@@ -30,11 +34,8 @@ pub trait ProcessSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
     fn exit_group(&mut self, status: libc::c_int) -> ! {
         self.trace("exit_group", 1);
 
-        #[allow(unused_must_use)]
-        loop {
-            unsafe { self.proxy(request!(libc::SYS_exit_group => status)) };
-            self.attacked();
-        }
+        let _ = unsafe { self.proxy(request!(libc::SYS_exit_group => status)) };
+        self.attacked()
     }
 
     /// Do a set_tid_address() syscall
@@ -95,6 +96,46 @@ pub trait ProcessSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         Ok(Default::default())
     }
 
+    /// Do a set_robust_list() syscall
+    ///
+    /// The robust futex list lives in the Keep's memory, which the host kernel
+    /// must not walk. So, the list head is only recorded for
+    /// `get_robust_list()` and never proxied.
+    fn set_robust_list(&mut self, head: UntrustedRef<RobustListHead>, len: libc::size_t) -> Result {
+        self.trace("set_robust_list", 2);
+
+        if len != size_of::<RobustListHead>() {
+            return Err(libc::EINVAL);
+        }
+
+        *self.robust_list_head() = head.as_ptr() as usize;
+
+        Ok(Default::default())
+    }
+
+    /// Do a get_robust_list() syscall
+    ///
+    /// Returns the list head recorded by `set_robust_list()`.
+    fn get_robust_list(
+        &mut self,
+        pid: libc::c_int,
+        head_ptr: UntrustedRefMut<usize>,
+        len_ptr: UntrustedRefMut<libc::size_t>,
+    ) -> Result {
+        self.trace("get_robust_list", 3);
+
+        match pid as usize {
+            0 | FAKE_PID => {}
+            _ => return Err(libc::ESRCH),
+        }
+
+        let head = *self.robust_list_head();
+        *(head_ptr.validate(self).ok_or(libc::EFAULT)?) = head;
+        *(len_ptr.validate(self).ok_or(libc::EFAULT)?) = size_of::<RobustListHead>();
+
+        Ok(Default::default())
+    }
+
     /// Do a sigaltstack() syscall
     ///
     /// This is currently unimplemented and returns success.
@@ -113,6 +154,138 @@ pub trait ProcessSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         Ok([FAKE_PID.into(), 0.into()])
     }
 
+    /// Do a getppid() syscall
+    ///
+    /// The parent of the keep lives outside of it, so report 0, like Linux
+    /// does for a parent in a different PID namespace.
+    fn getppid(&mut self) -> Result {
+        self.trace("getppid", 0);
+        Ok(Default::default())
+    }
+
+    /// Do a gettid() syscall
+    ///
+    /// We are only using one thread, so the thread id is the process id.
+    fn gettid(&mut self) -> Result {
+        self.trace("gettid", 0);
+        Ok([FAKE_PID.into(), 0.into()])
+    }
+
+    /// Do a getsid() syscall
+    ///
+    /// The keep is the leader of its own session.
+    fn getsid(&mut self, pid: libc::pid_t) -> Result {
+        self.trace("getsid", 1);
+        match pid as usize {
+            0 | FAKE_PID => Ok([FAKE_PID.into(), 0.into()]),
+            _ => Err(libc::ESRCH),
+        }
+    }
+
+    /// Yields to a scheduler running inside the keep
+    ///
+    /// Returns `true` if a scheduler was run, in which case `sched_yield()`
+    /// is not proxied to the host.
+    /// Defaults to `false`, for keeps without a scheduler of their own.
+    fn yield_to_scheduler(&mut self) -> bool {
+        false
+    }
+
+    /// Do a sched_yield() syscall
+    fn sched_yield(&mut self) -> Result {
+        self.trace("sched_yield", 0);
+        if self.yield_to_scheduler() {
+            return Ok(Default::default());
+        }
+        unsafe { self.proxy(request!(libc::SYS_sched_yield)) }
+    }
+
+    /// The `RLIMIT_STACK` limit reported to the keep
+    ///
+    /// If `Some`, `prlimit64()` and `getrlimit()` queries for `RLIMIT_STACK`
+    /// are answered with this value without proxying to the host.
+    /// Defaults to `None`, which proxies all resources.
+    fn stack_limit(&self) -> Option<libc::rlimit> {
+        None
+    }
+
+    /// Do a prlimit64() syscall
+    fn prlimit64(
+        &mut self,
+        pid: libc::pid_t,
+        resource: libc::c_int,
+        new_limit: UntrustedRef<libc::rlimit>,
+        old_limit: UntrustedRefMut<libc::rlimit>,
+    ) -> Result {
+        self.trace("prlimit64", 4);
+
+        match pid as usize {
+            0 | FAKE_PID => {}
+            _ => return Err(libc::ESRCH),
+        }
+
+        if resource == libc::RLIMIT_STACK as libc::c_int {
+            if let Some(limit) = self.stack_limit() {
+                if !new_limit.as_ptr().is_null() {
+                    return Err(libc::EPERM);
+                }
+
+                if !old_limit.as_ptr().is_null() {
+                    *(old_limit.validate(self).ok_or(libc::EFAULT)?) = limit;
+                }
+
+                return Ok(Default::default());
+            }
+        }
+
+        let new_limit = if new_limit.as_ptr().is_null() {
+            None
+        } else {
+            Some(*new_limit.validate(self).ok_or(libc::EFAULT)?)
+        };
+
+        let c = self.new_cursor();
+
+        let (c, host_new_limit) = match new_limit {
+            None => (c, 0),
+            Some(ref new_limit) => {
+                let (c, buf) = c.write(new_limit).or(Err(libc::EMSGSIZE))?;
+                (c, Self::translate_shim_to_host_addr(buf as *const _))
+            }
+        };
+
+        let host_old_limit = if old_limit.as_ptr().is_null() {
+            0
+        } else {
+            let (_, buf) = c.alloc::<libc::rlimit>(1).or(Err(libc::EMSGSIZE))?;
+            Self::translate_shim_to_host_addr(buf[0].as_ptr())
+        };
+
+        // `pid` refers to the keep itself, which is the calling process on the host
+        let ret = unsafe {
+            self.proxy(
+                request!(libc::SYS_prlimit64 => 0, resource, host_new_limit, host_old_limit),
+            )?
+        };
+
+        if !old_limit.as_ptr().is_null() {
+            let c = self.new_cursor();
+            let c = match new_limit {
+                None => c,
+                Some(_) => c.alloc::<libc::rlimit>(1).or(Err(libc::EMSGSIZE))?.0,
+            };
+            let (_, limit) = unsafe { c.read::<libc::rlimit>().or(Err(libc::EMSGSIZE))? };
+            *(old_limit.validate(self).ok_or(libc::EFAULT)?) = limit;
+        }
+
+        Ok(ret)
+    }
+
+    /// Do a getrlimit() syscall
+    fn getrlimit(&mut self, resource: libc::c_int, rlim: UntrustedRefMut<libc::rlimit>) -> Result {
+        self.prlimit64(0, resource, UntrustedRef::from(core::ptr::null()), rlim)
+    }
+
     /// Do a getuid() syscall
     fn getuid(&mut self) -> Result {
         self.trace("getuid", 0);
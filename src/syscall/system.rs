@@ -3,11 +3,23 @@
 //! system syscalls
 
 use super::BaseSyscallHandler;
-use crate::untrusted::{AddressValidator, UntrustedRefMut, Validate, ValidateSlice};
+use crate::untrusted::{AddressValidator, UntrustedRef, UntrustedRefMut, Validate, ValidateSlice};
 use crate::{request, Result};
 
 /// system syscalls
 pub trait SystemSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
+    /// Draws 64 random bits for `getrandom()`
+    ///
+    /// Returns `None` if no entropy is available right now.
+    /// Defaults to the `rdrand` instruction.
+    fn rdrand64(&mut self) -> Option<u64> {
+        let mut el = 0u64;
+        match unsafe { core::arch::x86_64::_rdrand64_step(&mut el) } {
+            1 => Some(el),
+            _ => None,
+        }
+    }
+
     /// Do a getrandom() syscall
     fn getrandom(
         &mut self,
@@ -16,26 +28,24 @@ pub trait SystemSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         flags: libc::c_uint,
     ) -> Result {
         self.trace("getrandom", 3);
-        let flags = flags & !(libc::GRND_NONBLOCK | libc::GRND_RANDOM);
-
-        if flags != 0 {
+        if flags & !(libc::GRND_NONBLOCK | libc::GRND_RANDOM) != 0 {
             return Err(libc::EINVAL);
         }
 
         let trusted = buf.validate_slice(buflen, self).ok_or(libc::EFAULT)?;
 
         for (i, chunk) in trusted.chunks_mut(8).enumerate() {
-            let mut el = 0u64;
             loop {
-                if unsafe { core::arch::x86_64::_rdrand64_step(&mut el) } == 1 {
+                if let Some(el) = self.rdrand64() {
                     chunk.copy_from_slice(&el.to_ne_bytes()[..chunk.len()]);
                     break;
                 } else {
-                    if (flags & libc::GRND_NONBLOCK) != 0 {
+                    if (flags & libc::GRND_NONBLOCK) != 0 && i == 0 {
                         //eprintln!("SC> getrandom(…) = -EAGAIN");
                         return Err(libc::EAGAIN);
                     }
-                    if (flags & libc::GRND_RANDOM) != 0 {
+                    // Like the kernel, report what was already copied as a short count
+                    if (flags & (libc::GRND_NONBLOCK | libc::GRND_RANDOM)) != 0 {
                         //eprintln!("SC> getrandom(…) = {}", i.checked_mul(8).unwrap());
                         return Ok([i.checked_mul(8).unwrap().into(), 0.into()]);
                     }
@@ -54,6 +64,7 @@ pub trait SystemSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
         tp: UntrustedRefMut<libc::timespec>,
     ) -> Result {
         self.trace("clock_gettime", 2);
+        let tp = tp.validate(self).ok_or(libc::EFAULT)?;
         let c = self.new_cursor();
 
         let (_, buf) = c.alloc::<libc::timespec>(1).or(Err(libc::EMSGSIZE))?;
@@ -64,11 +75,64 @@ pub trait SystemSyscallHandler: BaseSyscallHandler + AddressValidator + Sized {
             unsafe { self.proxy(request!(libc::SYS_clock_gettime => clockid, host_virt))? };
 
         let c = self.new_cursor();
-        *(tp.validate(self).ok_or(libc::EFAULT)?) = unsafe { c.read().or(Err(libc::EMSGSIZE))?.1 };
+        *tp = unsafe { c.read().or(Err(libc::EMSGSIZE))?.1 };
 
         Ok(result)
     }
 
+    /// syscall
+    fn nanosleep(
+        &mut self,
+        req: UntrustedRef<libc::timespec>,
+        rem: UntrustedRefMut<libc::timespec>,
+    ) -> Result {
+        // Linux measures nanosleep() against CLOCK_MONOTONIC
+        self.clock_nanosleep(libc::CLOCK_MONOTONIC, 0, req, rem)
+    }
+
+    /// syscall
+    fn clock_nanosleep(
+        &mut self,
+        clockid: libc::clockid_t,
+        flags: libc::c_int,
+        req: UntrustedRef<libc::timespec>,
+        rem: UntrustedRefMut<libc::timespec>,
+    ) -> Result {
+        self.trace("clock_nanosleep", 4);
+        let req = req.validate(self).ok_or(libc::EFAULT)?;
+
+        // The remaining time is only ever reported for a relative sleep
+        let rem = if rem.as_ptr().is_null() || flags & libc::TIMER_ABSTIME != 0 {
+            None
+        } else {
+            Some(rem.validate(self).ok_or(libc::EFAULT)?)
+        };
+
+        let c = self.new_cursor();
+        let (c, block_req) = c.write(req).or(Err(libc::EMSGSIZE))?;
+        let host_req = Self::translate_shim_to_host_addr(block_req as *const libc::timespec);
+        let host_rem = match rem {
+            None => 0,
+            Some(_) => {
+                let (_, block_rem) = c.alloc::<libc::timespec>(1).or(Err(libc::EMSGSIZE))?;
+                Self::translate_shim_to_host_addr(block_rem[0].as_ptr())
+            }
+        };
+
+        let result = unsafe {
+            self.proxy(request!(libc::SYS_clock_nanosleep => clockid, flags, host_req, host_rem))
+        };
+
+        // The kernel only writes `rem` if the sleep was interrupted
+        if let (Err(libc::EINTR), Some(rem)) = (result, rem) {
+            let c = self.new_cursor();
+            let (c, _) = c.alloc::<libc::timespec>(1).or(Err(libc::EMSGSIZE))?;
+            *rem = unsafe { c.read().or(Err(libc::EMSGSIZE))?.1 };
+        }
+
+        result
+    }
+
     /// Do a uname() system call
     fn uname(&mut self, buf: UntrustedRefMut<libc::utsname>) -> Result {
         self.trace("uname", 1);
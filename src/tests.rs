@@ -4,7 +4,7 @@ use super::*;
 
 #[test]
 fn req_size() {
-    assert_eq!(size_of::<Request>(), size_of::<usize>() * 8);
+    assert_eq!(size_of::<Request>(), size_of::<[usize; 8]>());
 }
 
 #[test]
@@ -14,7 +14,7 @@ fn rep_size() {
 
 #[test]
 fn msg_size() {
-    assert_eq!(size_of::<Message>(), size_of::<usize>() * 8);
+    assert_eq!(size_of::<Message>(), size_of::<[usize; 8]>());
 }
 
 #[test]
@@ -30,7 +30,6 @@ fn buf_capacity() {
 
 #[test]
 #[cfg_attr(miri, ignore)]
-#[cfg(feature = "asm")]
 fn syscall() {
     // Test syscall failure, including bidirectional conversion.
     let req = request!(libc::SYS_close => -1isize);
@@ -51,6 +50,329 @@ fn syscall() {
     assert_eq!(0usize, res);
 }
 
+#[test]
+#[cfg_attr(miri, ignore)]
+fn syscall_clock_gettime() {
+    let mut tp = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let tp_ptr = &mut tp as *mut libc::timespec;
+
+    let req = request!(libc::SYS_clock_gettime => libc::CLOCK_MONOTONIC, tp_ptr);
+    let rep = unsafe { req.syscall() };
+    assert_eq!(rep, Ok([0.into(), 0.into()]).into());
+    assert!(tp.tv_sec != 0 || tp.tv_nsec != 0);
+
+    let req = request!(libc::SYS_clock_gettime => -1isize, tp_ptr);
+    let rep = unsafe { req.syscall() };
+    assert_eq!(rep, Err(libc::EINVAL).into());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn syscall_large_return() {
+    use std::os::unix::io::AsRawFd;
+
+    // /proc/self/mem has unsigned offsets, so this lseek() returns a negative `off_t`.
+    let mem = std::fs::File::open("/proc/self/mem").unwrap();
+    let offset = 0x8000_0000_0000_1000usize;
+    let req = request!(libc::SYS_lseek => mem.as_raw_fd(), offset, libc::SEEK_SET);
+    let rep = unsafe { req.syscall() };
+    let ret: usize = Result::from(rep).unwrap()[0].into();
+    assert_eq!(ret, offset);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn syscall_filtered() {
+    // A denied syscall fails with EPERM, although the kernel would have allowed it.
+    let req = request!(libc::SYS_getpid);
+    let rep = unsafe { req.syscall_filtered(&Filter::empty()) };
+    assert_eq!(usize::from(rep.ret[0]), -libc::EPERM as usize);
+    assert_eq!(Result::from(rep), Err(libc::EPERM));
+
+    let mut filter = Filter::empty();
+    filter.allow(libc::SYS_getpid);
+    let rep = unsafe { req.syscall_filtered(&filter) };
+    let pid: usize = Result::from(rep).unwrap()[0].into();
+    assert_eq!(pid, std::process::id() as usize);
+
+    let filter = Filter::default();
+    for &num in &[
+        libc::SYS_read,
+        libc::SYS_write,
+        libc::SYS_openat,
+        libc::SYS_close,
+    ] {
+        assert!(filter.allows(num));
+    }
+    for &num in &[
+        libc::SYS_execve,
+        libc::SYS_kill,
+        libc::SYS_ptrace,
+        libc::SYS_getpid,
+    ] {
+        assert!(!filter.allows(num));
+    }
+    assert!(!filter.allows(-1));
+    assert!(!filter.allows(syscall::SYS_ENARX_GETATT));
+
+    // Without the filter, dup() would have returned a new fd.
+    let req = request!(libc::SYS_dup => 0usize);
+    let rep = unsafe { req.syscall_filtered(&Filter::empty()) };
+    assert_eq!(Result::from(rep), Err(libc::EPERM));
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn syscall_restricted() {
+    let policy = Policy::read_only();
+    let path = b"/dev/null\0";
+    let openat =
+        |flags: libc::c_int| request!(libc::SYS_openat => libc::AT_FDCWD, path.as_ptr(), flags);
+
+    // Reading is allowed.
+    let rep = unsafe { openat(libc::O_RDONLY).syscall_restricted(&policy) };
+    let fd: usize = Result::from(rep).unwrap()[0].into();
+    let rep = unsafe { request!(libc::SYS_close => fd).syscall_restricted(&policy) };
+    assert!(Result::from(rep).is_ok());
+
+    // Writing is refused, although the kernel would have allowed it.
+    for &flags in &[
+        libc::O_WRONLY,
+        libc::O_RDWR,
+        libc::O_RDONLY | libc::O_CREAT,
+        libc::O_RDONLY | libc::O_TRUNC,
+    ] {
+        assert_eq!(policy.check(&openat(flags)), Err(libc::EROFS));
+        let rep = unsafe { openat(flags).syscall_restricted(&policy) };
+        assert_eq!(usize::from(rep.ret[0]), -libc::EROFS as usize);
+        assert_eq!(Result::from(rep), Err(libc::EROFS));
+    }
+
+    let open = request!(libc::SYS_open => path.as_ptr(), libc::O_WRONLY);
+    assert_eq!(policy.check(&open), Err(libc::EROFS));
+    let open = request!(libc::SYS_open => path.as_ptr(), libc::O_RDONLY);
+    assert_eq!(policy.check(&open), Ok(()));
+
+    let buf = [0u8; 1];
+    let write = request!(libc::SYS_write => 1, buf.as_ptr(), 0);
+    assert_eq!(policy.check(&write), Err(libc::EROFS));
+    let unlink = request!(libc::SYS_unlink => path.as_ptr());
+    assert_eq!(policy.check(&unlink), Err(libc::EROFS));
+    let rename = request!(libc::SYS_rename => path.as_ptr(), path.as_ptr());
+    assert_eq!(policy.check(&rename), Err(libc::EROFS));
+
+    // Syscalls the shim never proxies are refused, including ones which
+    // would modify files around the checks above.
+    for &num in &[
+        libc::SYS_utime,
+        libc::SYS_utimes,
+        libc::SYS_futimesat,
+        libc::SYS_utimensat,
+        libc::SYS_open_by_handle_at,
+        libc::SYS_openat2,
+        libc::SYS_io_uring_setup,
+        libc::SYS_io_uring_enter,
+        libc::SYS_pwrite64,
+        libc::SYS_writev,
+        libc::SYS_ioctl,
+        libc::SYS_getpid,
+    ] {
+        assert_eq!(policy.check(&request!(num)), Err(libc::EROFS));
+    }
+    let rep = unsafe { request!(libc::SYS_io_uring_setup => 1, 0).syscall_restricted(&policy) };
+    assert_eq!(Result::from(rep), Err(libc::EROFS));
+
+    // Read-only syscalls the shim proxies are still permitted.
+    for &num in &[
+        libc::SYS_read,
+        libc::SYS_fstat,
+        libc::SYS_lseek,
+        libc::SYS_close,
+    ] {
+        assert_eq!(policy.check(&request!(num)), Ok(()));
+    }
+
+    // The default policy permits everything.
+    let rep = unsafe { openat(libc::O_WRONLY).syscall_restricted(&Policy::default()) };
+    let fd: usize = Result::from(rep).unwrap()[0].into();
+    let rep = unsafe { request!(libc::SYS_close => fd).syscall() };
+    assert!(Result::from(rep).is_ok());
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn syscall_restricted_open_flags() {
+    let path = b"/dev/null\0";
+    let openat = |flags: libc::c_int, mode: libc::mode_t| request!(libc::SYS_openat => libc::AT_FDCWD, path.as_ptr(), flags, mode);
+
+    // O_CREAT without a mode
+    let mut policy = Policy::default();
+    policy.reject_creat_without_mode();
+    assert_eq!(policy.check(&openat(libc::O_CREAT, 0)), Err(libc::EINVAL));
+    let rep = unsafe { openat(libc::O_RDWR | libc::O_CREAT, 0).syscall_restricted(&policy) };
+    assert_eq!(usize::from(rep.ret[0]), -libc::EINVAL as usize);
+    assert_eq!(policy.check(&openat(libc::O_CREAT, 0o600)), Ok(()));
+    assert_eq!(policy.check(&openat(libc::O_RDONLY, 0)), Ok(()));
+    let open = request!(libc::SYS_open => path.as_ptr(), libc::O_CREAT, 0);
+    assert_eq!(policy.check(&open), Err(libc::EINVAL));
+    assert_eq!(Policy::default().check(&openat(libc::O_CREAT, 0)), Ok(()));
+
+    // O_TMPFILE
+    let mut policy = Policy::default();
+    policy.reject_tmpfile();
+    let tmpfile = libc::O_TMPFILE | libc::O_RDWR;
+    assert_eq!(policy.check(&openat(tmpfile, 0o600)), Err(libc::EINVAL));
+    let rep = unsafe { openat(tmpfile, 0o600).syscall_restricted(&policy) };
+    assert_eq!(Result::from(rep), Err(libc::EINVAL));
+    // O_DIRECTORY alone shares a bit with O_TMPFILE, but is not rejected.
+    assert_eq!(policy.check(&openat(libc::O_DIRECTORY, 0)), Ok(()));
+    assert_eq!(Policy::default().check(&openat(tmpfile, 0o600)), Ok(()));
+
+    // O_DIRECT
+    let mut policy = Policy::default();
+    policy.strip_direct();
+    let mut req = openat(libc::O_RDONLY | libc::O_DIRECT | libc::O_CLOEXEC, 0);
+    assert_eq!(policy.check(&req), Ok(()));
+    policy.sanitize(&mut req);
+    assert_eq!(req, openat(libc::O_RDONLY | libc::O_CLOEXEC, 0));
+    let mut req = request!(libc::SYS_read => 0, libc::O_DIRECT);
+    policy.sanitize(&mut req);
+    assert_eq!(req, request!(libc::SYS_read => 0, libc::O_DIRECT));
+
+    // /dev/null doesn't support O_DIRECT, so only the stripped open succeeds.
+    let req = openat(libc::O_RDONLY | libc::O_DIRECT, 0);
+    let rep = unsafe { req.syscall() };
+    assert_eq!(Result::from(rep), Err(libc::EINVAL));
+    let rep = unsafe { req.syscall_restricted(&policy) };
+    let fd: usize = Result::from(rep).unwrap()[0].into();
+    let rep = unsafe { request!(libc::SYS_close => fd).syscall() };
+    assert!(Result::from(rep).is_ok());
+
+    // The options combine.
+    let mut policy = Policy::default();
+    policy
+        .reject_creat_without_mode()
+        .reject_tmpfile()
+        .strip_direct();
+    assert_eq!(
+        policy.check(&openat(libc::O_CREAT | libc::O_DIRECT, 0)),
+        Err(libc::EINVAL)
+    );
+}
+
+/// Confines paths under a prefix directory
+struct Sandbox(Vec<u8>);
+
+impl PathFilter for Sandbox {
+    fn filter(&self, path: &mut [u8], len: usize) -> core::result::Result<(), libc::c_int> {
+        if path[..len].windows(2).any(|w| w == b"..") {
+            return Err(libc::EACCES);
+        }
+        if self.0.len() + len >= path.len() {
+            return Err(libc::ENAMETOOLONG);
+        }
+
+        path.copy_within(..=len, self.0.len());
+        path[..self.0.len()].copy_from_slice(&self.0);
+        Ok(())
+    }
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn syscall_path_filtered() {
+    use std::io::Read;
+    use std::os::unix::{ffi::OsStrExt, io::FromRawFd};
+
+    let dir = std::env::temp_dir().join(format!("sallyport-sandbox-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("hello"), b"sandboxed").unwrap();
+    let sandbox = Sandbox(dir.as_os_str().as_bytes().to_vec());
+
+    let mut block = Box::new(Block::default());
+    let stage = |block: &mut Block, path: &[u8]| {
+        let (_, buf) = block.cursor().copy_from_slice(path).unwrap();
+        buf.as_ptr() as usize
+    };
+
+    // The open hits the rewritten path.
+    let path = stage(&mut block, b"/hello\0");
+    block.msg.req = request!(libc::SYS_openat => libc::AT_FDCWD, path, libc::O_RDONLY);
+    let rep = unsafe { block.syscall_path_filtered(&sandbox) };
+    let fd: usize = Result::from(rep).unwrap()[0].into();
+    let mut file = unsafe { std::fs::File::from_raw_fd(fd as _) };
+    let mut content = String::new();
+    file.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "sandboxed");
+
+    // The path was rewritten in place.
+    let staged = unsafe { std::ffi::CStr::from_ptr(path as _) };
+    assert_eq!(staged.to_bytes(), dir.join("hello").as_os_str().as_bytes());
+
+    let path = stage(&mut block, b"/hello\0");
+    block.msg.req = request!(libc::SYS_open => path, libc::O_RDONLY);
+    let rep = unsafe { block.syscall_path_filtered(&sandbox) };
+    let fd: usize = Result::from(rep).unwrap()[0].into();
+    drop(unsafe { std::fs::File::from_raw_fd(fd as _) });
+
+    // The filter refuses a path.
+    let path = stage(&mut block, b"/../hello\0");
+    block.msg.req = request!(libc::SYS_openat => libc::AT_FDCWD, path, libc::O_RDONLY);
+    let rep = unsafe { block.syscall_path_filtered(&sandbox) };
+    assert_eq!(Result::from(rep), Err(libc::EACCES));
+
+    // Paths must be staged in the block and NUL-terminated.
+    let outside = b"/hello\0";
+    block.msg.req = request!(libc::SYS_openat => libc::AT_FDCWD, outside.as_ptr(), 0);
+    let rep = unsafe { block.syscall_path_filtered(&sandbox) };
+    assert_eq!(Result::from(rep), Err(libc::EFAULT));
+
+    let path = stage(&mut block, &[b'x'; Block::buf_capacity()]);
+    block.msg.req = request!(libc::SYS_openat => libc::AT_FDCWD, path, 0);
+    let rep = unsafe { block.syscall_path_filtered(&sandbox) };
+    assert_eq!(Result::from(rep), Err(libc::ENAMETOOLONG));
+
+    // Syscalls without a path are issued unchanged.
+    block.msg.req = request!(libc::SYS_getpid);
+    let rep = unsafe { block.syscall_path_filtered(&sandbox) };
+    let pid: usize = Result::from(rep).unwrap()[0].into();
+    assert_eq!(pid, std::process::id() as usize);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn syscall_mapped() {
+    // 500 is not an x86-64 syscall, so only the map makes it run getpid().
+    let mut map = NumberMap::default();
+    map.map(500, libc::SYS_getpid);
+    assert_eq!(map.translate(500), libc::SYS_getpid);
+
+    let req = request!(500);
+    let rep = unsafe { req.syscall_mapped(&map) };
+    let pid: usize = Result::from(rep).unwrap()[0].into();
+    assert_eq!(pid, std::process::id() as usize);
+
+    let rep = unsafe { req.syscall() };
+    assert_eq!(Result::from(rep), Err(libc::ENOSYS));
+
+    // Unmapped numbers pass through unchanged.
+    assert_eq!(map.translate(libc::SYS_getpid), libc::SYS_getpid);
+    assert_eq!(map.translate(-1), -1);
+    assert_eq!(
+        map.translate(syscall::SYS_ENARX_GETATT),
+        syscall::SYS_ENARX_GETATT
+    );
+
+    let req = request!(libc::SYS_close => -1isize);
+    let rep = unsafe { req.syscall_mapped(&map) };
+    assert_eq!(Result::from(rep), Err(libc::EBADF));
+}
+
 #[test]
 fn request() {
     let req = request!(0 => 1, 2, 3, 4, 5, 6, 7, 8, 9);
@@ -251,3 +573,1322 @@ fn copy_into_slice() -> std::result::Result<(), OutOfSpace> {
 
     Ok(())
 }
+
+/// A shim-side handler, which proxies requests to a mock host
+struct TestHandler {
+    block: Box<Block>,
+    host: Box<dyn FnMut(&Request) -> Result>,
+    requests: Vec<Request>,
+    robust_list_head: usize,
+    /// Words of entropy left before one empty draw, after which the pool is refilled
+    ///
+    /// `None` is a pool, which never runs empty.
+    entropy: Option<usize>,
+    stack_limit: Option<libc::rlimit>,
+    yields: Option<usize>,
+}
+
+impl TestHandler {
+    fn new(host: impl FnMut(&Request) -> Result + 'static) -> Self {
+        Self {
+            block: Box::new(Block::default()),
+            host: Box::new(host),
+            requests: Vec::new(),
+            robust_list_head: 0,
+            entropy: None,
+            stack_limit: None,
+            yields: None,
+        }
+    }
+
+    fn syscall(&mut self, nr: libc::c_long, args: &[usize]) -> Result {
+        let arg = |i: usize| Register::<usize>::from(args.get(i).copied().unwrap_or_default());
+        syscall::SyscallHandler::syscall(
+            self,
+            arg(0),
+            arg(1),
+            arg(2),
+            arg(3),
+            arg(4),
+            arg(5),
+            nr as _,
+        )
+    }
+}
+
+impl untrusted::AddressValidator for TestHandler {
+    fn validate_const_mem_fn(&self, _ptr: *const (), _size: usize) -> bool {
+        true
+    }
+
+    fn validate_mut_mem_fn(&self, _ptr: *mut (), _size: usize) -> bool {
+        true
+    }
+}
+
+impl syscall::BaseSyscallHandler for TestHandler {
+    unsafe fn proxy(&mut self, req: Request) -> Result {
+        self.requests.push(req);
+        (self.host)(&req)
+    }
+
+    fn attacked(&mut self) -> ! {
+        panic!("attacked by the host")
+    }
+
+    fn translate_shim_to_host_addr<T>(buf: *const T) -> usize {
+        buf as _
+    }
+
+    fn new_cursor(&mut self) -> Cursor<'_> {
+        self.block.cursor()
+    }
+
+    fn unknown_syscall(
+        &mut self,
+        _a: Register<usize>,
+        _b: Register<usize>,
+        _c: Register<usize>,
+        _d: Register<usize>,
+        _e: Register<usize>,
+        _f: Register<usize>,
+        _nr: usize,
+    ) {
+    }
+
+    fn trace(&mut self, _name: &str, _argc: usize) {}
+}
+
+impl syscall::MemorySyscallHandler for TestHandler {
+    fn brk(&mut self, _addr: *const u8) -> Result {
+        Err(libc::ENOSYS)
+    }
+
+    fn mmap(
+        &mut self,
+        _addr: untrusted::UntrustedRef<u8>,
+        _length: libc::size_t,
+        _prot: libc::c_int,
+        _flags: libc::c_int,
+        _fd: libc::c_int,
+        _offset: libc::off_t,
+    ) -> Result {
+        Err(libc::ENOSYS)
+    }
+
+    fn munmap(&mut self, _addr: untrusted::UntrustedRef<u8>, _length: libc::size_t) -> Result {
+        Err(libc::ENOSYS)
+    }
+
+    fn madvise(
+        &mut self,
+        _addr: *const libc::c_void,
+        _length: libc::size_t,
+        _advice: libc::c_int,
+    ) -> Result {
+        Err(libc::ENOSYS)
+    }
+
+    fn mprotect(
+        &mut self,
+        _addr: untrusted::UntrustedRef<u8>,
+        _len: libc::size_t,
+        _prot: libc::c_int,
+    ) -> Result {
+        Err(libc::ENOSYS)
+    }
+}
+
+impl syscall::ProcessSyscallHandler for TestHandler {
+    fn arch_prctl(&mut self, _code: libc::c_int, _addr: libc::c_ulong) -> Result {
+        Err(libc::ENOSYS)
+    }
+
+    fn robust_list_head(&mut self) -> &mut usize {
+        &mut self.robust_list_head
+    }
+
+    fn stack_limit(&self) -> Option<libc::rlimit> {
+        self.stack_limit
+    }
+
+    fn yield_to_scheduler(&mut self) -> bool {
+        match self.yields.as_mut() {
+            Some(yields) => {
+                *yields += 1;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl syscall::EnarxSyscallHandler for TestHandler {
+    fn get_attestation(
+        &mut self,
+        _nonce: untrusted::UntrustedRef<u8>,
+        _nonce_len: libc::size_t,
+        _buf: untrusted::UntrustedRefMut<u8>,
+        _buf_len: libc::size_t,
+    ) -> Result {
+        Err(libc::ENOSYS)
+    }
+}
+
+impl syscall::FileSyscallHandler for TestHandler {}
+impl syscall::NetworkSyscallHandler for TestHandler {}
+impl syscall::SystemSyscallHandler for TestHandler {
+    fn rdrand64(&mut self) -> Option<u64> {
+        match self.entropy.as_mut() {
+            Some(0) => {
+                self.entropy = None;
+                None
+            }
+            Some(words) => {
+                *words -= 1;
+                Some(u64::from_ne_bytes([0xaa; 8]))
+            }
+            None => Some(u64::from_ne_bytes([0xaa; 8])),
+        }
+    }
+}
+impl syscall::SyscallHandler for TestHandler {}
+
+/// Returns a mock host, which must not be reached
+fn unreachable_host() -> impl FnMut(&Request) -> Result {
+    |req| panic!("unexpected request to the host: {:?}", req)
+}
+
+#[test]
+fn getppid_gettid_getsid() {
+    let mut handler = TestHandler::new(unreachable_host());
+
+    let ret = handler.syscall(libc::SYS_getppid, &[]).unwrap();
+    assert_eq!(usize::from(ret[0]), 0);
+
+    let ret = handler.syscall(libc::SYS_gettid, &[]).unwrap();
+    assert_eq!(usize::from(ret[0]), syscall::FAKE_PID);
+
+    let ret = handler.syscall(libc::SYS_getsid, &[0]).unwrap();
+    assert_eq!(usize::from(ret[0]), syscall::FAKE_PID);
+
+    let ret = handler
+        .syscall(libc::SYS_getsid, &[syscall::FAKE_PID])
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), syscall::FAKE_PID);
+
+    assert_eq!(
+        handler.syscall(libc::SYS_getsid, &[syscall::FAKE_PID + 1]),
+        Err(libc::ESRCH)
+    );
+    assert!(handler.requests.is_empty());
+}
+
+#[test]
+fn readahead() {
+    let mut handler = TestHandler::new(|req| match usize::from(req.arg[0]) {
+        3 => Ok(Default::default()),
+        _ => Err(libc::EBADF),
+    });
+
+    assert!(handler
+        .syscall(libc::SYS_readahead, &[3, 4096, 512])
+        .is_ok());
+    assert_eq!(
+        handler.requests.pop(),
+        Some(request!(libc::SYS_readahead => 3usize, 4096usize, 512usize))
+    );
+
+    assert_eq!(
+        handler.syscall(libc::SYS_readahead, &[4, 0, 512]),
+        Err(libc::EBADF)
+    );
+}
+
+#[test]
+fn sync_syncfs() {
+    let mut handler = TestHandler::new(|req| match usize::from(req.num) as _ {
+        libc::SYS_sync => Ok(Default::default()),
+        _ => Err(libc::EBADF),
+    });
+
+    assert!(handler.syscall(libc::SYS_sync, &[]).is_ok());
+    assert_eq!(handler.requests.pop(), Some(request!(libc::SYS_sync)));
+
+    assert_eq!(handler.syscall(libc::SYS_syncfs, &[3]), Err(libc::EBADF));
+    assert_eq!(
+        handler.requests.pop(),
+        Some(request!(libc::SYS_syncfs => 3usize))
+    );
+}
+
+#[test]
+fn prlimit64_stack_limit() {
+    const NOFILE: libc::rlimit = libc::rlimit {
+        rlim_cur: 1024,
+        rlim_max: 4096,
+    };
+    const STACK: libc::rlimit = libc::rlimit {
+        rlim_cur: 8 << 20,
+        rlim_max: libc::RLIM_INFINITY,
+    };
+
+    let mut handler = TestHandler::new(|req| {
+        assert_eq!(usize::from(req.arg[0]), 0);
+        assert_eq!(usize::from(req.arg[1]), libc::RLIMIT_NOFILE as usize);
+        assert_eq!(usize::from(req.arg[2]), 0);
+        unsafe { *(usize::from(req.arg[3]) as *mut libc::rlimit) = NOFILE };
+        Ok(Default::default())
+    });
+    handler.stack_limit = Some(STACK);
+
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    let ptr = &mut limit as *mut libc::rlimit as usize;
+
+    // RLIMIT_STACK is answered by the shim.
+    let stack = libc::RLIMIT_STACK as usize;
+    assert!(handler.syscall(libc::SYS_getrlimit, &[stack, ptr]).is_ok());
+    assert_eq!(
+        (limit.rlim_cur, limit.rlim_max),
+        (STACK.rlim_cur, STACK.rlim_max)
+    );
+    assert!(handler.requests.is_empty());
+
+    // Setting it is refused.
+    assert_eq!(
+        handler.syscall(libc::SYS_prlimit64, &[0, stack, ptr, 0]),
+        Err(libc::EPERM)
+    );
+    assert!(handler.requests.is_empty());
+
+    // Other resources still reach the host.
+    let nofile = libc::RLIMIT_NOFILE as usize;
+    let pid = syscall::FAKE_PID;
+    assert!(handler
+        .syscall(libc::SYS_prlimit64, &[pid, nofile, 0, ptr])
+        .is_ok());
+    assert_eq!(
+        (limit.rlim_cur, limit.rlim_max),
+        (NOFILE.rlim_cur, NOFILE.rlim_max)
+    );
+    assert_eq!(handler.requests.len(), 1);
+}
+
+#[test]
+fn memfd_create() {
+    let mut handler = TestHandler::new(|req| {
+        let name = unsafe { std::ffi::CStr::from_ptr(usize::from(req.arg[0]) as _) };
+        assert_eq!(name.to_bytes(), b"keep");
+        assert_eq!(usize::from(req.arg[1]), libc::MFD_CLOEXEC as usize);
+        Ok([3.into(), 0.into()])
+    });
+
+    let name = b"keep\0";
+    let ret = handler
+        .syscall(
+            libc::SYS_memfd_create,
+            &[name.as_ptr() as _, libc::MFD_CLOEXEC as _],
+        )
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 3);
+
+    // The name was staged in the block, not passed by reference.
+    let staged = usize::from(handler.requests[0].arg[0]);
+    assert_ne!(staged, name.as_ptr() as usize);
+
+    // Names longer than the kernel allows are rejected before reaching the host.
+    let long = [b'x'; 256];
+    assert_eq!(
+        handler.syscall(libc::SYS_memfd_create, &[long.as_ptr() as _, 0]),
+        Err(libc::EINVAL)
+    );
+    assert_eq!(handler.requests.len(), 1);
+}
+
+#[test]
+fn accept4_truncated_addr() {
+    let mut handler = TestHandler::new(|req| {
+        let addr = libc::sockaddr_in {
+            sin_family: libc::AF_INET as _,
+            sin_port: 8080u16.to_be(),
+            sin_addr: libc::in_addr { s_addr: 0 },
+            sin_zero: [0; 8],
+        };
+        let full = size_of::<libc::sockaddr_in>() as libc::socklen_t;
+
+        // Like the kernel, copy at most the buffer length, but report the full length.
+        unsafe {
+            let addrlen = usize::from(req.arg[2]) as *mut libc::socklen_t;
+            let len = (*addrlen).min(full) as usize;
+            let src = &addr as *const libc::sockaddr_in as *const u8;
+            src.copy_to(usize::from(req.arg[1]) as *mut u8, len);
+            *addrlen = full;
+        }
+
+        Ok([4.into(), 0.into()])
+    });
+
+    let mut addr = [0xffu8; 4];
+    let mut addrlen = addr.len() as libc::socklen_t;
+
+    let ret = handler
+        .syscall(
+            libc::SYS_accept4,
+            &[3, addr.as_mut_ptr() as _, &mut addrlen as *mut _ as _, 0],
+        )
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 4);
+
+    let family = libc::sa_family_t::from_ne_bytes([addr[0], addr[1]]);
+    assert_eq!(family, libc::AF_INET as libc::sa_family_t);
+    assert_eq!(&addr[2..], &8080u16.to_be_bytes());
+    assert_eq!(addrlen as usize, size_of::<libc::sockaddr_in>());
+}
+
+#[test]
+fn accept4_no_addr() {
+    let mut handler = TestHandler::new(|_| Ok([4.into(), 0.into()]));
+
+    let mut addrlen = 16 as libc::socklen_t;
+    let ret = handler
+        .syscall(
+            libc::SYS_accept4,
+            &[3, 0, &mut addrlen as *mut _ as _, libc::SOCK_CLOEXEC as _],
+        )
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 4);
+    assert_eq!(addrlen, 16);
+    assert_eq!(
+        handler.requests.pop(),
+        Some(request!(libc::SYS_accept4 => 3, 0, 0, libc::SOCK_CLOEXEC))
+    );
+}
+
+#[test]
+fn accept4_zero_addrlen() {
+    let mut handler = TestHandler::new(|req| {
+        // The kernel copies nothing into a zero-length buffer, but reports the full length.
+        unsafe {
+            let addrlen = usize::from(req.arg[2]) as *mut libc::socklen_t;
+            assert_eq!(*addrlen, 0);
+            *addrlen = size_of::<libc::sockaddr_in>() as _;
+        }
+
+        Ok([4.into(), 0.into()])
+    });
+
+    let mut addr = [0xffu8; 4];
+    let mut addrlen = 0 as libc::socklen_t;
+
+    let ret = handler
+        .syscall(
+            libc::SYS_accept4,
+            &[3, addr.as_mut_ptr() as _, &mut addrlen as *mut _ as _, 0],
+        )
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 4);
+    assert_eq!(addr, [0xff; 4]);
+    assert_eq!(addrlen as usize, size_of::<libc::sockaddr_in>());
+}
+
+#[test]
+fn getsockname_zero_addrlen() {
+    let mut handler = TestHandler::new(|req| {
+        unsafe {
+            let addrlen = usize::from(req.arg[2]) as *mut libc::socklen_t;
+            assert_eq!(*addrlen, 0);
+            *addrlen = size_of::<libc::sockaddr_in>() as _;
+        }
+
+        Ok([0.into(), 0.into()])
+    });
+
+    let mut addr = [0xffu8; 4];
+    let mut addrlen = 0 as libc::socklen_t;
+
+    assert!(handler
+        .syscall(
+            libc::SYS_getsockname,
+            &[3, addr.as_mut_ptr() as _, &mut addrlen as *mut _ as _],
+        )
+        .is_ok());
+    assert_eq!(addr, [0xff; 4]);
+    assert_eq!(addrlen as usize, size_of::<libc::sockaddr_in>());
+}
+
+#[test]
+fn iovcnt_limit() {
+    use syscall::FileSyscallHandler;
+
+    let mut handler = TestHandler::new(|req| Ok([req.arg[2], 0.into()]));
+
+    let buf = [0u8; 1];
+    let iov = vec![
+        libc::iovec {
+            iov_base: buf.as_ptr() as _,
+            iov_len: buf.len(),
+        };
+        TestHandler::MAX_IOVCNT as usize + 1
+    ];
+
+    for nr in [libc::SYS_readv, libc::SYS_writev].iter().copied() {
+        assert_eq!(
+            handler.syscall(nr, &[3, iov.as_ptr() as _, iov.len()]),
+            Err(libc::EINVAL)
+        );
+        assert_eq!(
+            handler.syscall(nr, &[3, iov.as_ptr() as _, -1isize as _]),
+            Err(libc::EINVAL)
+        );
+    }
+    assert!(handler.requests.is_empty());
+
+    let ret = handler
+        .syscall(libc::SYS_writev, &[3, iov.as_ptr() as _, iov.len() - 1])
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), iov.len() - 1);
+}
+
+#[test]
+fn restart_errno() {
+    for err in [
+        ERESTARTSYS,
+        ERESTARTNOINTR,
+        ERESTARTNOHAND,
+        ERESTART_RESTARTBLOCK,
+    ]
+    .iter()
+    .copied()
+    {
+        let rep = Reply::from(Err(err));
+        assert_eq!(usize::from(rep.ret[0]), -libc::EINTR as usize);
+        assert_eq!(Result::from(rep), Err(libc::EINTR));
+    }
+
+    assert_eq!(
+        Result::from(Reply::from(Err(libc::EBADF))),
+        Err(libc::EBADF)
+    );
+}
+
+#[test]
+fn close_range() {
+    let mut handler = TestHandler::new(|req| match usize::from(req.arg[2]) {
+        0 => Ok(Default::default()),
+        _ => Err(libc::EINVAL),
+    });
+
+    assert!(handler
+        .syscall(libc::SYS_close_range, &[3, libc::c_uint::MAX as _, 0])
+        .is_ok());
+    assert_eq!(
+        handler.requests.pop(),
+        Some(request!(libc::SYS_close_range => 3usize, libc::c_uint::MAX, 0usize))
+    );
+
+    assert_eq!(
+        handler.syscall(libc::SYS_close_range, &[3, 4, 0xff]),
+        Err(libc::EINVAL)
+    );
+}
+
+#[test]
+fn restart_syscall() {
+    let req = request!(libc::SYS_restart_syscall);
+    let rep = unsafe { req.syscall() };
+    assert_eq!(Result::from(rep), Err(libc::EINTR));
+}
+
+#[test]
+fn fcntl_pipe_sz() {
+    let mut size = 4096usize;
+    let mut handler = TestHandler::new(move |req| {
+        assert_eq!(usize::from(req.num), libc::SYS_fcntl as usize);
+        match usize::from(req.arg[1]) as _ {
+            libc::F_GETPIPE_SZ => Ok([size.into(), 0.into()]),
+            libc::F_SETPIPE_SZ => {
+                // Like the kernel, round up to whole pages.
+                size = (usize::from(req.arg[2]) + Page::SIZE - 1) & !(Page::SIZE - 1);
+                Ok([size.into(), 0.into()])
+            }
+            _ => Err(libc::EINVAL),
+        }
+    });
+
+    let set = libc::F_SETPIPE_SZ as usize;
+    let get = libc::F_GETPIPE_SZ as usize;
+
+    let ret = handler.syscall(libc::SYS_fcntl, &[3, set, 65000]).unwrap();
+    assert_eq!(usize::from(ret[0]), 65536);
+
+    let ret = handler.syscall(libc::SYS_fcntl, &[3, get]).unwrap();
+    assert_eq!(usize::from(ret[0]), 65536);
+    assert_eq!(handler.requests.len(), 2);
+
+    // The Keep's stdio are not host pipes.
+    for fd in 0..=2 {
+        assert_eq!(
+            handler.syscall(libc::SYS_fcntl, &[fd, get]),
+            Err(libc::EINVAL)
+        );
+        assert_eq!(
+            handler.syscall(libc::SYS_fcntl, &[fd, set, 65536]),
+            Err(libc::EINVAL)
+        );
+    }
+    assert_eq!(handler.requests.len(), 2);
+}
+
+#[test]
+fn open_openat() {
+    let mut handler = TestHandler::new(|req| {
+        let path = unsafe { std::ffi::CStr::from_ptr(usize::from(req.arg[1]) as _) };
+        match path.to_bytes() {
+            b"/etc/hosts" | b"data/input" => Ok([5.into(), 0.into()]),
+            _ => Err(libc::EACCES),
+        }
+    });
+
+    let flags = (libc::O_RDONLY | libc::O_CLOEXEC) as usize;
+
+    // Absolute path
+    let path = b"/etc/hosts\0";
+    let ret = handler
+        .syscall(libc::SYS_open, &[path.as_ptr() as _, flags, 0])
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 5);
+    let req = handler.requests.pop().unwrap();
+    assert_eq!(usize::from(req.num), libc::SYS_openat as usize);
+    assert_eq!(usize::from(req.arg[0]) as libc::c_int, libc::AT_FDCWD);
+    assert_ne!(usize::from(req.arg[1]), path.as_ptr() as usize);
+    assert_eq!(usize::from(req.arg[2]), flags);
+
+    // Relative path
+    let path = b"data/input\0";
+    let ret = handler
+        .syscall(libc::SYS_openat, &[3, path.as_ptr() as _, flags, 0])
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 5);
+    assert_eq!(usize::from(handler.requests.pop().unwrap().arg[0]), 3);
+
+    // Errors from the host are passed through
+    let path = b"/etc/shadow\0";
+    let flags = (libc::O_WRONLY | libc::O_CREAT) as usize;
+    assert_eq!(
+        handler.syscall(libc::SYS_openat, &[3, path.as_ptr() as _, flags, 0o600]),
+        Err(libc::EACCES)
+    );
+    assert_eq!(usize::from(handler.requests.pop().unwrap().arg[3]), 0o600);
+
+    // Overlong paths never reach the host
+    let path = vec![b'a'; libc::PATH_MAX as usize];
+    assert_eq!(
+        handler.syscall(libc::SYS_open, &[path.as_ptr() as _, 0, 0]),
+        Err(libc::ENAMETOOLONG)
+    );
+    assert!(handler.requests.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "attacked by the host")]
+fn openat_invalid_fd() {
+    let fd = libc::c_int::MAX as usize + 1;
+    let mut handler = TestHandler::new(move |_| Ok([fd.into(), 0.into()]));
+
+    let path = b"/etc/hosts\0";
+    let _ = handler.syscall(libc::SYS_openat, &[3, path.as_ptr() as _, 0, 0]);
+}
+
+#[test]
+#[should_panic(expected = "attacked by the host")]
+fn memfd_create_invalid_fd() {
+    let fd = libc::c_int::MAX as usize + 1;
+    let mut handler = TestHandler::new(move |_| Ok([fd.into(), 0.into()]));
+
+    let name = b"keep\0";
+    let _ = handler.syscall(libc::SYS_memfd_create, &[name.as_ptr() as _, 0]);
+}
+
+#[test]
+fn robust_list() {
+    let mut handler = TestHandler::new(unreachable_host());
+
+    let head = syscall::RobustListHead {
+        list: core::ptr::null(),
+        futex_offset: -32,
+        list_op_pending: core::ptr::null(),
+    };
+    let head_ptr = &head as *const _ as usize;
+    let len = size_of::<syscall::RobustListHead>();
+
+    assert_eq!(
+        handler.syscall(libc::SYS_set_robust_list, &[head_ptr, len - 1]),
+        Err(libc::EINVAL)
+    );
+    assert!(handler
+        .syscall(libc::SYS_set_robust_list, &[head_ptr, len])
+        .is_ok());
+
+    let mut got_head = 0usize;
+    let mut got_len = 0usize;
+    let args = [
+        0,
+        &mut got_head as *mut _ as usize,
+        &mut got_len as *mut _ as usize,
+    ];
+    assert!(handler.syscall(libc::SYS_get_robust_list, &args).is_ok());
+    assert_eq!(got_head, head_ptr);
+    assert_eq!(got_len, len);
+
+    let args = [syscall::FAKE_PID + 1, args[1], args[2]];
+    assert_eq!(
+        handler.syscall(libc::SYS_get_robust_list, &args),
+        Err(libc::ESRCH)
+    );
+    assert!(handler.requests.is_empty());
+
+    // The head is kept per handler, not shared between them.
+    let mut other = TestHandler::new(unreachable_host());
+    let args = [0, args[1], args[2]];
+    assert!(other.syscall(libc::SYS_get_robust_list, &args).is_ok());
+    assert_eq!(got_head, 0);
+}
+
+#[test]
+fn close() {
+    let mut handler = TestHandler::new(|req| match usize::from(req.arg[0]) {
+        3 => Ok(Default::default()),
+        _ => Err(libc::EBADF),
+    });
+
+    assert_eq!(
+        handler.syscall(libc::SYS_close, &[3]).map(|ret| ret[0]),
+        Ok(0.into())
+    );
+    assert_eq!(
+        handler.requests.pop(),
+        Some(request!(libc::SYS_close => 3usize))
+    );
+
+    assert_eq!(handler.syscall(libc::SYS_close, &[4]), Err(libc::EBADF));
+}
+
+#[test]
+fn readv_partial() {
+    fn host(data: &'static [u8]) -> impl FnMut(&Request) -> Result {
+        let mut pos = 0;
+        move |req| {
+            assert_eq!(usize::from(req.num), libc::SYS_read as usize);
+            if pos == data.len() {
+                return Err(libc::EAGAIN);
+            }
+            let len = usize::from(req.arg[2]).min(data.len() - pos);
+            let dst = usize::from(req.arg[1]) as *mut u8;
+            unsafe { dst.copy_from(data[pos..].as_ptr(), len) };
+            pos += len;
+            Ok([len.into(), 0.into()])
+        }
+    }
+
+    let mut a = [0u8; 4];
+    let mut b = [0u8; 8];
+    let mut c = [0u8; 8];
+    let iov = [
+        libc::iovec {
+            iov_base: a.as_mut_ptr() as _,
+            iov_len: a.len(),
+        },
+        libc::iovec {
+            iov_base: b.as_mut_ptr() as _,
+            iov_len: b.len(),
+        },
+        libc::iovec {
+            iov_base: c.as_mut_ptr() as _,
+            iov_len: c.len(),
+        },
+    ];
+
+    // The first buffer is filled, the second one only partially: stop there.
+    let mut handler = TestHandler::new(host(b"hello!"));
+    let ret = handler
+        .syscall(libc::SYS_readv, &[3, iov.as_ptr() as _, iov.len()])
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 6);
+    assert_eq!(&a, b"hell");
+    assert_eq!(&b[..2], b"o!");
+    assert_eq!(c, [0; 8]);
+    assert_eq!(handler.requests.len(), 2);
+
+    // An error after some bytes were read reports those bytes.
+    let mut handler = TestHandler::new(host(b"0123456789ab"));
+    let ret = handler
+        .syscall(libc::SYS_readv, &[3, iov.as_ptr() as _, iov.len()])
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 12);
+    assert_eq!(&a, b"0123");
+    assert_eq!(&b, b"456789ab");
+    assert_eq!(handler.requests.len(), 3);
+
+    // An error before anything was read is reported as is.
+    let mut handler = TestHandler::new(host(b""));
+    assert_eq!(
+        handler.syscall(libc::SYS_readv, &[3, iov.as_ptr() as _, iov.len()]),
+        Err(libc::EAGAIN)
+    );
+}
+
+#[test]
+fn poll() {
+    let mut handler = TestHandler::new(|req| {
+        let nfds = usize::from(req.arg[1]);
+        if nfds == 0 {
+            return Ok([0.into(), 0.into()]);
+        }
+        let fds = usize::from(req.arg[0]) as *mut libc::pollfd;
+        let fds = unsafe { std::slice::from_raw_parts_mut(fds, nfds) };
+        for fd in fds.iter_mut() {
+            fd.revents = fd.events & libc::POLLIN;
+        }
+        Ok([nfds.into(), 0.into()])
+    });
+
+    let mut fds = [libc::pollfd {
+        fd: 3,
+        events: libc::POLLIN,
+        revents: 0,
+    }];
+    let ret = handler
+        .syscall(libc::SYS_poll, &[fds.as_mut_ptr() as _, fds.len(), 100])
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 1);
+    assert_eq!(fds[0].revents, libc::POLLIN);
+
+    // A timeout-only poll doesn't need any fds.
+    let ret = handler.syscall(libc::SYS_poll, &[0, 0, 100]).unwrap();
+    assert_eq!(usize::from(ret[0]), 0);
+    assert_eq!(
+        handler.requests.pop(),
+        Some(request!(libc::SYS_poll => 0usize, 0usize, 100usize))
+    );
+}
+
+#[test]
+fn socket() {
+    let mut handler = TestHandler::new(|req| match usize::from(req.arg[0]) as libc::c_int {
+        libc::AF_INET => Ok([3.into(), 0.into()]),
+        _ => Err(libc::EAFNOSUPPORT),
+    });
+
+    let ret = handler
+        .syscall(
+            libc::SYS_socket,
+            &[libc::AF_INET as _, libc::SOCK_STREAM as _, 0],
+        )
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 3);
+    assert_eq!(
+        handler.requests.pop(),
+        Some(request!(libc::SYS_socket => libc::AF_INET, libc::SOCK_STREAM, 0))
+    );
+
+    assert_eq!(
+        handler.syscall(
+            libc::SYS_socket,
+            &[libc::AF_APPLETALK as _, libc::SOCK_STREAM as _, 0],
+        ),
+        Err(libc::EAFNOSUPPORT)
+    );
+}
+
+#[test]
+fn bind_connect() {
+    let mut handler = TestHandler::new(|req| {
+        let addr = usize::from(req.arg[1]) as *const u8;
+        let addrlen = usize::from(req.arg[2]);
+        let addr = unsafe { std::slice::from_raw_parts(addr, addrlen) };
+
+        let family = libc::sa_family_t::from_ne_bytes([addr[0], addr[1]]);
+        match family as libc::c_int {
+            libc::AF_INET if addrlen == size_of::<libc::sockaddr_in>() => {
+                assert_eq!(&addr[2..4], &8080u16.to_be_bytes());
+                assert_eq!(&addr[4..8], &[127, 0, 0, 1]);
+            }
+            libc::AF_UNIX => assert_eq!(&addr[2..], b"\0sallyport"),
+            _ => return Err(libc::EINVAL),
+        }
+        Ok([0.into(), 0.into()])
+    });
+
+    let inet = libc::sockaddr_in {
+        sin_family: libc::AF_INET as _,
+        sin_port: 8080u16.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: u32::from_ne_bytes([127, 0, 0, 1]),
+        },
+        sin_zero: [0; 8],
+    };
+    let inet_len = size_of::<libc::sockaddr_in>();
+    for &num in &[libc::SYS_bind, libc::SYS_connect] {
+        let ret = handler
+            .syscall(num, &[3, &inet as *const _ as _, inet_len])
+            .unwrap();
+        assert_eq!(usize::from(ret[0]), 0);
+    }
+
+    // An abstract unix socket address is shorter than `sockaddr_un`, and
+    // the kernel must see exactly the length the caller passed.
+    let mut unix: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    unix.sun_family = libc::AF_UNIX as _;
+    for (dst, src) in unix.sun_path.iter_mut().zip(b"\0sallyport") {
+        *dst = *src as _;
+    }
+    let unix_len = size_of::<libc::sa_family_t>() + b"\0sallyport".len();
+    for &num in &[libc::SYS_bind, libc::SYS_connect] {
+        let ret = handler
+            .syscall(num, &[3, &unix as *const _ as _, unix_len])
+            .unwrap();
+        assert_eq!(usize::from(ret[0]), 0);
+        assert_eq!(
+            usize::from(handler.requests.pop().unwrap().arg[2]),
+            unix_len
+        );
+    }
+}
+
+#[test]
+fn sendto_recvfrom() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // A loopback "socket": sendto queues a datagram and its source, recvfrom pops it.
+    type Datagram = (Vec<u8>, Vec<u8>);
+
+    let queue: Rc<RefCell<Vec<Datagram>>> = Default::default();
+    let host_queue = queue.clone();
+    let mut handler = TestHandler::new(move |req| match i64::from(req.num) {
+        libc::SYS_sendto => {
+            let buf = usize::from(req.arg[1]) as *const u8;
+            let count = usize::from(req.arg[2]);
+            let addr = usize::from(req.arg[4]) as *const u8;
+            let addrlen = usize::from(req.arg[5]);
+            let (buf, addr) = unsafe {
+                (
+                    std::slice::from_raw_parts(buf, count).to_vec(),
+                    std::slice::from_raw_parts(addr, addrlen).to_vec(),
+                )
+            };
+            host_queue.borrow_mut().push((buf, addr));
+            Ok([count.into(), 0.into()])
+        }
+        libc::SYS_recvfrom => {
+            let (data, addr) = host_queue.borrow_mut().pop().ok_or(libc::EAGAIN)?;
+            let count = usize::from(req.arg[2]).min(data.len());
+            unsafe {
+                data.as_ptr()
+                    .copy_to(usize::from(req.arg[1]) as *mut u8, count);
+                let addrlen = usize::from(req.arg[5]) as *mut libc::socklen_t;
+                if !addrlen.is_null() {
+                    let len = (*addrlen as usize).min(addr.len());
+                    addr.as_ptr()
+                        .copy_to(usize::from(req.arg[4]) as *mut u8, len);
+                    *addrlen = addr.len() as _;
+                }
+            }
+            Ok([count.into(), 0.into()])
+        }
+        _ => Err(libc::ENOSYS),
+    });
+
+    let dest = libc::sockaddr_in {
+        sin_family: libc::AF_INET as _,
+        sin_port: 5353u16.to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: u32::from_ne_bytes([127, 0, 0, 1]),
+        },
+        sin_zero: [0; 8],
+    };
+    let dest_len = size_of::<libc::sockaddr_in>();
+    let data = b"datagram";
+
+    let ret = handler
+        .syscall(
+            libc::SYS_sendto,
+            &[
+                3,
+                data.as_ptr() as _,
+                data.len(),
+                0,
+                &dest as *const _ as _,
+                dest_len,
+            ],
+        )
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), data.len());
+
+    let mut buf = [0u8; 16];
+    let mut src: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    let mut src_len = dest_len as libc::socklen_t;
+    let ret = handler
+        .syscall(
+            libc::SYS_recvfrom,
+            &[
+                3,
+                buf.as_mut_ptr() as _,
+                buf.len(),
+                0,
+                &mut src as *mut _ as _,
+                &mut src_len as *mut _ as _,
+            ],
+        )
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), data.len());
+    assert_eq!(&buf[..data.len()], data);
+    assert_eq!(src_len as usize, dest_len);
+    assert_eq!(src.sin_port, dest.sin_port);
+    assert_eq!(src.sin_addr.s_addr, dest.sin_addr.s_addr);
+    assert!(queue.borrow().is_empty());
+
+    // A source address without a length to write it to is a fault, not a panic.
+    assert_eq!(
+        handler.syscall(
+            libc::SYS_recvfrom,
+            &[
+                3,
+                buf.as_mut_ptr() as _,
+                buf.len(),
+                0,
+                &mut src as *mut _ as _,
+                0
+            ],
+        ),
+        Err(libc::EFAULT)
+    );
+}
+
+#[test]
+fn setsockopt_getsockopt() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // The mock socket stores a single `SO_REUSEADDR` value.
+    let reuseaddr = Rc::new(Cell::new(0 as libc::c_int));
+    let host_reuseaddr = reuseaddr.clone();
+    let mut handler = TestHandler::new(move |req| {
+        let level = usize::from(req.arg[1]) as libc::c_int;
+        let optname = usize::from(req.arg[2]) as libc::c_int;
+        if (level, optname) != (libc::SOL_SOCKET, libc::SO_REUSEADDR) {
+            return Err(libc::ENOPROTOOPT);
+        }
+
+        let optval = usize::from(req.arg[3]) as *mut libc::c_int;
+        match i64::from(req.num) {
+            libc::SYS_setsockopt => host_reuseaddr.set(unsafe { optval.read_unaligned() }),
+            libc::SYS_getsockopt => unsafe {
+                let optlen = usize::from(req.arg[4]) as *mut libc::socklen_t;
+                let len = (*optlen as usize).min(size_of::<libc::c_int>());
+                let value = host_reuseaddr.get().to_ne_bytes();
+                value.as_ptr().copy_to(optval as *mut u8, len);
+                *optlen = len as _;
+            },
+            _ => return Err(libc::ENOSYS),
+        }
+        Ok([0.into(), 0.into()])
+    });
+
+    let on: libc::c_int = 1;
+    let ret = handler
+        .syscall(
+            libc::SYS_setsockopt,
+            &[
+                3,
+                libc::SOL_SOCKET as _,
+                libc::SO_REUSEADDR as _,
+                &on as *const _ as _,
+                size_of::<libc::c_int>(),
+            ],
+        )
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 0);
+    assert_eq!(reuseaddr.get(), 1);
+
+    let mut optval: libc::c_int = 0;
+    let mut optlen = size_of::<libc::c_int>() as libc::socklen_t;
+    let ret = handler
+        .syscall(
+            libc::SYS_getsockopt,
+            &[
+                3,
+                libc::SOL_SOCKET as _,
+                libc::SO_REUSEADDR as _,
+                &mut optval as *mut _ as _,
+                &mut optlen as *mut _ as _,
+            ],
+        )
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 0);
+    assert_eq!(optval, 1);
+    assert_eq!(optlen as usize, size_of::<libc::c_int>());
+
+    // A larger buffer, like a `timeval` for `SO_RCVTIMEO`, only gets what the host wrote.
+    let mut optval = [0xffu8; size_of::<libc::timeval>()];
+    let mut optlen = optval.len() as libc::socklen_t;
+    handler
+        .syscall(
+            libc::SYS_getsockopt,
+            &[
+                3,
+                libc::SOL_SOCKET as _,
+                libc::SO_REUSEADDR as _,
+                optval.as_mut_ptr() as _,
+                &mut optlen as *mut _ as _,
+            ],
+        )
+        .unwrap();
+    assert_eq!(optlen as usize, size_of::<libc::c_int>());
+    assert_eq!(&optval[..4], &1i32.to_ne_bytes());
+    assert!(optval[4..].iter().all(|&b| b == 0xff));
+}
+
+#[test]
+fn clock_gettime() {
+    // Let the real kernel answer, so the clock ids are passed through untouched.
+    let mut handler = TestHandler::new(|req| unsafe { req.syscall() }.into());
+
+    for &clockid in &[libc::CLOCK_REALTIME, libc::CLOCK_MONOTONIC] {
+        let mut tp = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: -1,
+        };
+        let ret = handler
+            .syscall(
+                libc::SYS_clock_gettime,
+                &[clockid as _, &mut tp as *mut _ as _],
+            )
+            .unwrap();
+        assert_eq!(usize::from(ret[0]), 0);
+        assert!((0..1_000_000_000).contains(&tp.tv_nsec));
+        if clockid == libc::CLOCK_REALTIME {
+            assert_ne!(tp.tv_sec, 0);
+        }
+    }
+
+    let mut handler = TestHandler::new(unreachable_host());
+    assert_eq!(
+        handler.syscall(libc::SYS_clock_gettime, &[libc::CLOCK_REALTIME as _, 0]),
+        Err(libc::EFAULT)
+    );
+}
+
+#[test]
+fn nanosleep() {
+    let mut handler = TestHandler::new(|req| {
+        let rem = usize::from(req.arg[3]) as *mut libc::timespec;
+        let sleep = unsafe { *(usize::from(req.arg[2]) as *const libc::timespec) };
+        if sleep.tv_sec < 1 {
+            return Ok([0.into(), 0.into()]);
+        }
+
+        // Pretend a signal arrived half way through.
+        if !rem.is_null() {
+            unsafe {
+                *rem = libc::timespec {
+                    tv_sec: sleep.tv_sec / 2,
+                    tv_nsec: 0,
+                }
+            };
+        }
+        Err(libc::EINTR)
+    });
+
+    let short = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 1000,
+    };
+    let mut rem = libc::timespec {
+        tv_sec: -1,
+        tv_nsec: -1,
+    };
+    let ret = handler
+        .syscall(
+            libc::SYS_nanosleep,
+            &[&short as *const _ as _, &mut rem as *mut _ as _],
+        )
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 0);
+    assert_eq!((rem.tv_sec, rem.tv_nsec), (-1, -1));
+    assert_eq!(
+        usize::from(handler.requests.pop().unwrap().arg[0]) as libc::clockid_t,
+        libc::CLOCK_MONOTONIC
+    );
+
+    let long = libc::timespec {
+        tv_sec: 10,
+        tv_nsec: 0,
+    };
+    assert_eq!(
+        handler.syscall(
+            libc::SYS_nanosleep,
+            &[&long as *const _ as _, &mut rem as *mut _ as _],
+        ),
+        Err(libc::EINTR)
+    );
+    assert_eq!((rem.tv_sec, rem.tv_nsec), (5, 0));
+
+    // An absolute sleep never reports the remaining time.
+    let mut rem = libc::timespec {
+        tv_sec: -1,
+        tv_nsec: -1,
+    };
+    assert_eq!(
+        handler.syscall(
+            libc::SYS_clock_nanosleep,
+            &[
+                libc::CLOCK_REALTIME as _,
+                libc::TIMER_ABSTIME as _,
+                &long as *const _ as _,
+                &mut rem as *mut _ as _,
+            ],
+        ),
+        Err(libc::EINTR)
+    );
+    assert_eq!((rem.tv_sec, rem.tv_nsec), (-1, -1));
+    assert_eq!(usize::from(handler.requests.pop().unwrap().arg[3]), 0);
+}
+
+#[test]
+fn getrandom() {
+    let mut handler = TestHandler::new(unreachable_host());
+
+    for &flags in &[0, libc::GRND_NONBLOCK, libc::GRND_RANDOM] {
+        // Exactly enough for the 8 chunks of the buffer
+        handler.entropy = Some(8);
+        let mut buf = [0u8; 61];
+        let ret = handler
+            .syscall(
+                libc::SYS_getrandom,
+                &[buf.as_mut_ptr() as _, buf.len(), flags as _],
+            )
+            .unwrap();
+        assert_eq!(usize::from(ret[0]), buf.len());
+        assert_eq!(&buf[..], &[0xaa; 61][..]);
+    }
+
+    let mut buf = [0u8; 8];
+    assert_eq!(
+        handler.syscall(
+            libc::SYS_getrandom,
+            &[buf.as_mut_ptr() as _, buf.len(), 0x80],
+        ),
+        Err(libc::EINVAL)
+    );
+
+    // With the pool empty, GRND_NONBLOCK fails with EAGAIN instead of blocking.
+    handler.entropy = Some(0);
+    let mut buf = [0u8; 61];
+    let args = [buf.as_mut_ptr() as _, buf.len(), libc::GRND_NONBLOCK as _];
+    assert_eq!(
+        handler.syscall(libc::SYS_getrandom, &args),
+        Err(libc::EAGAIN)
+    );
+
+    // Once part of the buffer was filled, that part is reported as a short count.
+    handler.entropy = Some(2);
+    let ret = handler.syscall(libc::SYS_getrandom, &args).unwrap();
+    assert_eq!(usize::from(ret[0]), 16);
+    assert_eq!(&buf[..16], &[0xaa; 16]);
+    assert_eq!(&buf[16..], &[0; 45]);
+
+    // GRND_RANDOM returns a short count of the bytes filled so far.
+    handler.entropy = Some(2);
+    let mut buf = [0u8; 61];
+    let args = [buf.as_mut_ptr() as _, buf.len(), libc::GRND_RANDOM as _];
+    let ret = handler.syscall(libc::SYS_getrandom, &args).unwrap();
+    assert_eq!(usize::from(ret[0]), 16);
+    assert_eq!(&buf[..16], &[0xaa; 16]);
+    assert_eq!(&buf[16..], &[0; 45]);
+    assert!(handler.requests.is_empty());
+}
+
+#[test]
+fn lseek() {
+    let mut handler = TestHandler::new(|req| match usize::from(req.arg[0]) {
+        3 => Ok([req.arg[1], 0.into()]),
+        _ => Err(libc::EBADF),
+    });
+
+    // Looks negative as an `off_t`, but is well outside the errno range.
+    let offset = 0x8000_0000_0000_1000usize;
+    let ret = handler
+        .syscall(libc::SYS_lseek, &[3, offset, libc::SEEK_SET as _])
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), offset);
+    assert_eq!(
+        handler.requests.pop(),
+        Some(request!(libc::SYS_lseek => 3, offset, libc::SEEK_SET))
+    );
+
+    assert_eq!(
+        handler.syscall(libc::SYS_lseek, &[4, 0, libc::SEEK_END as _]),
+        Err(libc::EBADF)
+    );
+}
+
+#[test]
+fn fstat() {
+    let mut handler = TestHandler::new(|req| {
+        let statbuf = usize::from(req.arg[1]) as *mut libc::stat;
+        assert_eq!(statbuf as usize % align_of::<libc::stat>(), 0);
+        match usize::from(req.arg[0]) {
+            3 => unsafe {
+                let mut st: libc::stat = std::mem::zeroed();
+                st.st_mode = libc::S_IFREG | 0o644;
+                st.st_size = 4242;
+                statbuf.write(st);
+                Ok(Default::default())
+            },
+            _ => Err(libc::EBADF),
+        }
+    });
+
+    let mut st: libc::stat = unsafe { std::mem::zeroed() };
+    let ret = handler
+        .syscall(libc::SYS_fstat, &[3, &mut st as *mut _ as _])
+        .unwrap();
+    assert_eq!(usize::from(ret[0]), 0);
+    assert_eq!(st.st_size, 4242);
+    assert_eq!(st.st_mode, libc::S_IFREG | 0o644);
+
+    assert_eq!(
+        handler.syscall(libc::SYS_fstat, &[9, &mut st as *mut _ as _]),
+        Err(libc::EBADF)
+    );
+
+    // The standard streams are still answered by the shim itself.
+    let requests = handler.requests.len();
+    handler
+        .syscall(libc::SYS_fstat, &[1, &mut st as *mut _ as _])
+        .unwrap();
+    assert_eq!(st.st_mode, libc::S_IFIFO | 0o600);
+    assert_eq!(handler.requests.len(), requests);
+}
+
+#[test]
+fn sched_yield() {
+    let mut handler = TestHandler::new(|_| Ok(Default::default()));
+
+    handler.syscall(libc::SYS_sched_yield, &[]).unwrap();
+    assert_eq!(
+        handler.requests.pop(),
+        Some(request!(libc::SYS_sched_yield))
+    );
+
+    // With a scheduler in the keep, nothing reaches the host.
+    handler.yields = Some(0);
+    handler.syscall(libc::SYS_sched_yield, &[]).unwrap();
+    handler.syscall(libc::SYS_sched_yield, &[]).unwrap();
+    assert_eq!(handler.yields, Some(2));
+    assert!(handler.requests.is_empty());
+}
@@ -63,7 +63,7 @@ impl<'a, T> UntrustedRef<'a, T> {
 
 impl<'a, T> From<*const T> for UntrustedRef<'a, T> {
     fn from(data: *const T) -> Self {
-        Self(data, PhantomData::default())
+        Self(data, PhantomData)
     }
 }
 
@@ -72,7 +72,7 @@ where
     usize: From<Register<U>>,
 {
     fn from(data: Register<U>) -> Self {
-        Self(usize::from(data) as _, PhantomData::default())
+        Self(usize::from(data) as _, PhantomData)
     }
 }
 
@@ -85,7 +85,7 @@ impl<'a, T> Validate for UntrustedRef<'a, T> {
         }
 
         // check for alignment
-        if self.0 as usize % align_of::<T>() != 0 {
+        if self.0 as usize & (align_of::<T>() - 1) != 0 {
             return None;
         }
 
@@ -116,7 +116,7 @@ impl<'a, T> ValidateSlice for UntrustedRef<'a, T> {
         }
 
         // check for alignment
-        if self.0 as usize % align_of::<T>() != 0 {
+        if self.0 as usize & (align_of::<T>() - 1) != 0 {
             return None;
         }
 
@@ -151,7 +151,7 @@ impl<'a, T> UntrustedRefMut<'a, T> {
 
 impl<'a, T> From<*mut T> for UntrustedRefMut<'a, T> {
     fn from(data: *mut T) -> Self {
-        Self(data, PhantomData::default())
+        Self(data, PhantomData)
     }
 }
 
@@ -160,7 +160,7 @@ where
     usize: From<Register<U>>,
 {
     fn from(data: Register<U>) -> Self {
-        Self(usize::from(data) as _, PhantomData::default())
+        Self(usize::from(data) as _, PhantomData)
     }
 }
 
@@ -173,7 +173,7 @@ impl<'a, T> Validate for UntrustedRefMut<'a, T> {
         }
 
         // check for alignment
-        if self.0 as usize % align_of::<T>() != 0 {
+        if self.0 as usize & (align_of::<T>() - 1) != 0 {
             return None;
         }
 
@@ -204,7 +204,7 @@ impl<'a, T> ValidateSlice for UntrustedRefMut<'a, T> {
         }
 
         // check for alignment
-        if self.0 as usize % align_of::<T>() != 0 {
+        if self.0 as usize & (align_of::<T>() - 1) != 0 {
             return None;
         }
 